@@ -0,0 +1,97 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Merging of type and group choice amendments (`/=` and `//=`) into the rules
+//! they extend.
+//!
+//! For example, `color = "red"` followed by `color /= "blue"` becomes
+//! `color = "red" / "blue"`, and `attrs = (a: int)` followed by
+//! `attrs //= (b: text)` becomes `attrs = (a: int // b: text)`.
+
+use std::collections::HashMap;
+
+use cddl::ast::{Group, GroupChoice, GroupEntry, OptionalComma, Rule, CDDL};
+
+/// Returns a copy of `cddl` in which every rule is defined once.
+///
+/// Amendments are appended as choices to the first rule with the same name,
+/// which keeps its position. Plugs of a socket (e.g. `$message-type /= "a"`)
+/// are merged the same way, and the engines keep the socket open. Rules of a
+/// different kind than the first one (e.g. a group amendment of a type rule)
+/// are left as they are.
+pub fn merge_amendments<'a>(cddl: &CDDL<'a>) -> CDDL<'a> {
+    let mut rules: Vec<Rule<'a>> = Vec::new();
+    let mut first = HashMap::new();
+    for rule in &cddl.rules {
+        let name = match rule {
+            Rule::Type { rule, .. } => &rule.name,
+            Rule::Group { rule, .. } => &rule.name,
+        };
+        let key = (name.ident, name.socket.is_some());
+        let Some(&index) = first.get(&key) else {
+            first.insert(key, rules.len());
+            rules.push(without_alternate(rule.clone()));
+            continue;
+        };
+        match (&mut rules[index], rule) {
+            (Rule::Type { rule: base, .. }, Rule::Type { rule, .. }) => {
+                base.value
+                    .type_choices
+                    .extend(rule.value.type_choices.iter().cloned());
+            }
+            (Rule::Group { rule: base, .. }, Rule::Group { rule, .. }) => {
+                let mut group_choices = group_choices(base.entry.clone());
+                group_choices.extend(self::group_choices(rule.entry.clone()));
+                base.entry = GroupEntry::InlineGroup {
+                    occur: None,
+                    group: Group {
+                        group_choices,
+                        span: Default::default(),
+                    },
+                    span: Default::default(),
+                    comments_before_group: None,
+                    comments_after_group: None,
+                };
+            }
+            _ => rules.push(rule.clone()),
+        }
+    }
+    CDDL {
+        rules,
+        comments: cddl.comments.clone(),
+    }
+}
+
+/// Turns an amendment without a rule to extend into a plain definition.
+fn without_alternate(mut rule: Rule) -> Rule {
+    match &mut rule {
+        Rule::Type { rule, .. } => rule.is_type_choice_alternate = false,
+        Rule::Group { rule, .. } => rule.is_group_choice_alternate = false,
+    }
+    rule
+}
+
+/// The group choices a group rule is made of.
+fn group_choices(entry: GroupEntry) -> Vec<GroupChoice> {
+    match entry {
+        GroupEntry::InlineGroup {
+            occur: None, group, ..
+        } => group.group_choices,
+        entry => vec![GroupChoice {
+            group_entries: vec![(entry, OptionalComma::default())],
+            span: Default::default(),
+            comments_before_grpchoice: None,
+        }],
+    }
+}
@@ -0,0 +1,117 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Conversions run from a Cargo build script.
+//!
+//! ```no_run
+//! // build.rs
+//! use cddlconv::{build::Builder, Target};
+//!
+//! fn main() {
+//!     Builder::new()
+//!         .file("spec.cddl")
+//!         .target(Target::Zod)
+//!         .generate()
+//!         .unwrap();
+//! }
+//! ```
+//!
+//! Every file is converted to a file of the same name in the output
+//! directory, with the extension of the target, e.g. `spec.ts` (or `spec.rs`
+//! for [`Target::Rust`]). Cargo reruns the script when an input changes, and
+//! shows the diagnostics as warnings.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+use crate::{
+    convert::{convert, Target},
+    options::Options,
+};
+
+/// Conversions of CDDL files to a [`Target`].
+#[derive(Debug, Clone)]
+pub struct Builder {
+    files: Vec<PathBuf>,
+    target: Target,
+    options: Options,
+    out_dir: Option<PathBuf>,
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Builder::new()
+    }
+}
+
+impl Builder {
+    /// Conversions to TypeScript with the default options, of no file yet.
+    pub fn new() -> Builder {
+        Builder {
+            files: Vec::new(),
+            target: Target::TypeScript,
+            options: Options::default(),
+            out_dir: None,
+        }
+    }
+    /// Adds a CDDL file to convert.
+    pub fn file(mut self, path: impl AsRef<Path>) -> Builder {
+        self.files.push(path.as_ref().to_path_buf());
+        self
+    }
+    pub fn target(mut self, target: Target) -> Builder {
+        self.target = target;
+        self
+    }
+    pub fn options(mut self, options: Options) -> Builder {
+        self.options = options;
+        self
+    }
+    /// Directory to write to, instead of `OUT_DIR`.
+    pub fn out_dir(mut self, dir: impl AsRef<Path>) -> Builder {
+        self.out_dir = Some(dir.as_ref().to_path_buf());
+        self
+    }
+    /// Converts the files and returns the paths written to.
+    pub fn generate(&self) -> Result<Vec<PathBuf>> {
+        let out_dir = match &self.out_dir {
+            Some(dir) => dir.clone(),
+            None => std::env::var_os("OUT_DIR").map(PathBuf::from).context(
+                "OUT_DIR is not set; run from a build script or set an output directory",
+            )?,
+        };
+        std::fs::create_dir_all(&out_dir)
+            .with_context(|| format!("failed to create {}", out_dir.display()))?;
+        let mut paths = Vec::new();
+        for file in &self.files {
+            println!("cargo:rerun-if-changed={}", file.display());
+            let source = std::fs::read_to_string(file)
+                .with_context(|| format!("failed to read {}", file.display()))?;
+            let output = convert(&source, self.target, &self.options)
+                .with_context(|| format!("failed to convert {}", file.display()))?;
+            for diagnostic in &output.diagnostics {
+                println!("cargo:warning={}: {}", file.display(), diagnostic);
+            }
+            let name = file
+                .file_name()
+                .with_context(|| format!("{} is not a file", file.display()))?;
+            let path = out_dir.join(Path::new(name).with_extension(self.target.extension()));
+            std::fs::write(&path, output.text)
+                .with_context(|| format!("failed to write {}", path.display()))?;
+            paths.push(path);
+        }
+        Ok(paths)
+    }
+}
@@ -0,0 +1,368 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The command line, which crates adding formats to a [`Registry`] can run
+//! as their own `main`.
+
+use crate::config::{self, Config};
+use crate::generator::Registry;
+use crate::options::{
+    Brands, Enums, Extensibility, Layout, Options, Profile, Quotes, Renames, TrailingCommas,
+};
+use crate::validate::{matching_rules, Instance};
+use anyhow::{bail, Context, Result};
+use cddl::ast::CDDL;
+use clap::builder::{PossibleValue, PossibleValuesParser};
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum ProfileType {
+    Json,
+    Cbor,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum ExtensibilityType {
+    Open,
+    Closed,
+    Strict,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum EnumsType {
+    ConstEnum,
+    Enum,
+    Union,
+    Object,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum TrailingCommasType {
+    All,
+    None,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum QuotesType {
+    Double,
+    Single,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Check JSON or CBOR documents against a rule. Files ending in `.cbor`
+    /// are read as CBOR, others as JSON.
+    Validate {
+        /// CDDL file to check against.
+        #[arg(long, value_name = "FILE")]
+        schema: PathBuf,
+        /// Rule to check against. If missing, the rules each document
+        /// matches are listed instead.
+        #[arg(long)]
+        rule: Option<String>,
+        /// Documents to check.
+        #[arg(required = true)]
+        instances: Vec<PathBuf>,
+    },
+}
+
+/// Simple program to greet a person
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None, args_conflicts_with_subcommands = true)]
+struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// File to convert.
+    file: Option<PathBuf>,
+    /// Configuration file describing the conversions to run, instead of a
+    /// file to convert. Defaults to `cddlconv.toml` if no file is given.
+    #[arg(short, long, value_name = "FILE", conflicts_with = "file")]
+    config: Option<PathBuf>,
+    /// Format to output.
+    #[arg(short, long, default_value = "typescript")]
+    format: String,
+    /// Emit template literal types for simple regular expressions.
+    #[arg(long)]
+    template_literals: bool,
+    /// Emit runtime type guards for every rule (TypeScript only).
+    #[arg(long)]
+    type_guards: bool,
+    /// Emit the primitive alias rule with the given name as a branded type.
+    /// May be repeated.
+    #[arg(long, value_name = "RULE")]
+    brand: Vec<String>,
+    /// Emit all primitive alias rules as branded types.
+    #[arg(long, conflicts_with = "brand")]
+    brand_all: bool,
+    /// Represent CBOR tags without a well-known mapping as `{tag, value}`
+    /// objects.
+    #[arg(long)]
+    cbor_tags: bool,
+    /// Representation of bytes and integers.
+    #[arg(long, value_enum, default_value_t = ProfileType::Json)]
+    profile: ProfileType,
+    /// Instantiate generic rules at each set of arguments they are used with.
+    #[arg(long)]
+    monomorphize: bool,
+    /// How catch-all members (e.g. `* tstr => any`) of maps are emitted.
+    #[arg(long, value_enum, default_value_t = ExtensibilityType::Open)]
+    extensibility: ExtensibilityType,
+    /// How choices of text values (e.g. `"cors" / "no-cors"`) are emitted
+    /// (TypeScript only).
+    #[arg(long, value_enum, default_value_t = EnumsType::ConstEnum)]
+    enums: EnumsType,
+    /// Emit tuples for groups used as elements of arrays (e.g. `[* pair]`).
+    #[arg(long)]
+    vector_groups: bool,
+    /// Number of spaces per level of indentation.
+    #[arg(long, default_value_t = 2)]
+    indent_width: usize,
+    /// Width that lines are kept within where possible.
+    #[arg(long, default_value_t = 80)]
+    line_width: usize,
+    /// Whether lists broken over several lines end with a comma.
+    #[arg(long, value_enum, default_value_t = TrailingCommasType::All)]
+    trailing_commas: TrailingCommasType,
+    /// Quotes of string literals.
+    #[arg(long, value_enum, default_value_t = QuotesType::Double)]
+    quotes: QuotesType,
+    /// TOML file with `rules` and `properties` tables of names to use instead
+    /// of the ones derived from the CDDL.
+    #[arg(long, value_name = "FILE")]
+    renames: Option<PathBuf>,
+    /// Name the rule `RULE` as `NAME`. May be repeated.
+    #[arg(long, value_name = "RULE=NAME")]
+    rename: Vec<String>,
+    /// Name the member with the key `KEY` in the rule `RULE` as `NAME`; its
+    /// value is still serialized under `KEY`. May be repeated.
+    #[arg(long, value_name = "RULE.KEY=NAME")]
+    rename_property: Vec<String>,
+}
+
+/// Parses the command line and runs the conversions it asks for, with the
+/// formats of `registry`.
+pub fn main(registry: &Registry) -> Result<()> {
+    let formats = registry.names().map(|(name, aliases)| {
+        PossibleValue::new(name.to_string()).aliases(aliases.iter().map(String::from))
+    });
+    let matches = Args::command()
+        .mut_arg("format", |arg| {
+            arg.value_parser(PossibleValuesParser::new(formats))
+        })
+        .get_matches();
+    let args = Args::from_arg_matches(&matches).unwrap_or_else(|error| error.exit());
+    if let Some(Command::Validate {
+        schema,
+        rule,
+        instances,
+    }) = &args.command
+    {
+        return validate(schema, rule.as_deref(), instances);
+    }
+    let Some(file) = &args.file else {
+        let path = args
+            .config
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(config::FILE_NAME));
+        return run(registry, &Config::load(&path)?);
+    };
+    let input = std::fs::read_to_string(file)?;
+    let cddl = cddl::parser::cddl_from_str(&input, true).map_err(anyhow::Error::msg)?;
+    let mut renames = match &args.renames {
+        Some(path) => toml::from_str(&std::fs::read_to_string(path)?)
+            .with_context(|| format!("failed to parse {}", path.display()))?,
+        None => Renames::default(),
+    };
+    for rename in &args.rename {
+        let (rule, name) = split_rename(rename)?;
+        renames.rules.insert(rule.to_string(), name.to_string());
+    }
+    for rename in &args.rename_property {
+        let (member, name) = split_rename(rename)?;
+        let (rule, key) = member
+            .rsplit_once('.')
+            .with_context(|| format!("expected RULE.KEY=NAME, found {}", rename))?;
+        renames
+            .properties
+            .entry(rule.to_string())
+            .or_default()
+            .insert(key.to_string(), name.to_string());
+    }
+    let options = Options {
+        template_literals: args.template_literals,
+        type_guards: args.type_guards,
+        brands: if args.brand_all {
+            Brands::All
+        } else if !args.brand.is_empty() {
+            Brands::Only(args.brand)
+        } else {
+            Brands::None
+        },
+        cbor_tags: args.cbor_tags,
+        profile: match args.profile {
+            ProfileType::Json => Profile::Json,
+            ProfileType::Cbor => Profile::Cbor,
+        },
+        monomorphize: args.monomorphize,
+        extensibility: match args.extensibility {
+            ExtensibilityType::Open => Extensibility::Open,
+            ExtensibilityType::Closed => Extensibility::Closed,
+            ExtensibilityType::Strict => Extensibility::Strict,
+        },
+        enums: match args.enums {
+            EnumsType::ConstEnum => Enums::ConstEnum,
+            EnumsType::Enum => Enums::Enum,
+            EnumsType::Union => Enums::Union,
+            EnumsType::Object => Enums::Object,
+        },
+        vector_groups: args.vector_groups,
+        layout: Layout {
+            indent_width: args.indent_width,
+            line_width: args.line_width,
+            trailing_commas: match args.trailing_commas {
+                TrailingCommasType::All => TrailingCommas::All,
+                TrailingCommasType::None => TrailingCommas::None,
+            },
+            quotes: match args.quotes {
+                QuotesType::Double => Quotes::Double,
+                QuotesType::Single => Quotes::Single,
+            },
+        },
+        renames,
+    };
+    generate(
+        registry,
+        &cddl,
+        &input,
+        &args.format,
+        &options,
+        std::io::stdout(),
+    )
+}
+
+/// Splits a `--rename` or `--rename-property` value at its `=`.
+fn split_rename(rename: &str) -> Result<(&str, &str)> {
+    rename
+        .split_once('=')
+        .with_context(|| format!("expected a name after `=` in {}", rename))
+}
+
+/// Runs the conversions of a configuration file, parsing each input once.
+fn run(registry: &Registry, config: &Config) -> Result<()> {
+    for conversion in config.conversions()? {
+        let input = std::fs::read_to_string(&conversion.input)
+            .with_context(|| format!("failed to read {}", conversion.input.display()))?;
+        let cddl = cddl::parser::cddl_from_str(&input, true)
+            .map_err(anyhow::Error::msg)
+            .with_context(|| format!("failed to parse {}", conversion.input.display()))?;
+        for output in conversion.outputs {
+            match &output.path {
+                Some(path) => {
+                    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+                        std::fs::create_dir_all(dir)?;
+                    }
+                    let file = std::fs::File::create(path)
+                        .with_context(|| format!("failed to create {}", path.display()))?;
+                    generate(
+                        registry,
+                        &cddl,
+                        &input,
+                        &output.format,
+                        &output.options,
+                        file,
+                    )
+                    .with_context(|| format!("failed to generate {}", path.display()))?;
+                }
+                None => generate(
+                    registry,
+                    &cddl,
+                    &input,
+                    &output.format,
+                    &output.options,
+                    std::io::stdout(),
+                )?,
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Checks `instances` against `rule` of `schema`, or lists the rules they
+/// match if there is no rule.
+fn validate(schema: &Path, rule: Option<&str>, instances: &[PathBuf]) -> Result<()> {
+    let input = std::fs::read_to_string(schema)
+        .with_context(|| format!("failed to read {}", schema.display()))?;
+    let cddl = cddl::parser::cddl_from_str(&input, true)
+        .map_err(anyhow::Error::msg)
+        .with_context(|| format!("failed to parse {}", schema.display()))?;
+    let mut failures = 0;
+    for path in instances {
+        let bytes =
+            std::fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+        let cbor = path
+            .extension()
+            .is_some_and(|extension| extension == "cbor");
+        let instance = Instance::parse(&bytes, cbor)
+            .with_context(|| format!("failed to parse {}", path.display()))?;
+        let Some(rule) = rule else {
+            let rules = matching_rules(&cddl, &instance);
+            if rules.is_empty() {
+                println!("{}: matches no rule", path.display());
+                failures += 1;
+            } else {
+                println!("{}: matches {}", path.display(), rules.join(", "));
+            }
+            continue;
+        };
+        let mismatches = crate::validate::validate(&cddl, rule, &instance)?;
+        if !mismatches.is_empty() {
+            failures += 1;
+        }
+        for mismatch in mismatches {
+            let pointer = if mismatch.path.is_empty() {
+                "/"
+            } else {
+                &mismatch.path
+            };
+            println!("{}: {}: {}", path.display(), pointer, mismatch.reason);
+        }
+    }
+    if failures > 0 {
+        bail!("{} of {} documents do not match", failures, instances.len());
+    }
+    Ok(())
+}
+
+/// Converts `cddl`, parsed from `source`, to `format` and writes the result
+/// to `out`.
+fn generate(
+    registry: &Registry,
+    cddl: &CDDL,
+    source: &str,
+    format: &str,
+    options: &Options,
+    mut out: impl Write,
+) -> Result<()> {
+    let generator = registry
+        .get(format)
+        .with_context(|| format!("unknown format {}", format))?;
+    for diagnostic in generator.generate(cddl, source, options, &mut out)? {
+        eprintln!("{}", diagnostic);
+    }
+    out.flush()?;
+
+    Ok(())
+}
@@ -0,0 +1,236 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Configuration files describing several conversions at once.
+//!
+//! A configuration file lists targets, each with a format, an output path and
+//! options. Options are named after the command-line flags, and those of a
+//! target override the shared ones. Targets converting the same input are
+//! generated from a single parse of it.
+//!
+//! ```toml
+//! input = "webdriver-bidi.cddl"
+//!
+//! [options]
+//! extensibility = "closed"
+//!
+//! [options.rename.rules]
+//! js-uint = "JsSafeUint"
+//!
+//! [[target]]
+//! format = "typescript"
+//! output = "src/gen/types.ts"
+//! options = { type-guards = true }
+//!
+//! [[target]]
+//! format = "zod"
+//! output = "src/gen/schemas.ts"
+//! ```
+
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+
+use crate::options::{
+    Brands, Enums, Extensibility, Layout, Options, Profile, Quotes, Renames, TrailingCommas,
+};
+
+/// Name of the configuration file used if none is given.
+pub const FILE_NAME: &str = "cddlconv.toml";
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Config {
+    /// CDDL file converted by targets without an input of their own.
+    pub input: Option<PathBuf>,
+    /// Options shared by all targets.
+    #[serde(default)]
+    pub options: OptionsConfig,
+    #[serde(rename = "target", default)]
+    pub targets: Vec<Target>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Target {
+    /// CDDL file to convert, if not the shared one.
+    pub input: Option<PathBuf>,
+    /// Name of the format in the [`crate::Registry`], e.g. `typescript`.
+    pub format: String,
+    /// File to write to, or standard output if missing.
+    pub output: Option<PathBuf>,
+    #[serde(default)]
+    pub options: OptionsConfig,
+}
+
+/// Options as written in a configuration file, each of which may be missing.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct OptionsConfig {
+    pub template_literals: Option<bool>,
+    pub type_guards: Option<bool>,
+    pub brand: Option<Vec<String>>,
+    pub brand_all: Option<bool>,
+    pub cbor_tags: Option<bool>,
+    pub profile: Option<Profile>,
+    pub monomorphize: Option<bool>,
+    pub extensibility: Option<Extensibility>,
+    pub enums: Option<Enums>,
+    pub vector_groups: Option<bool>,
+    pub indent_width: Option<usize>,
+    pub line_width: Option<usize>,
+    pub trailing_commas: Option<TrailingCommas>,
+    pub quotes: Option<Quotes>,
+    /// Renames (see [`Renames`]), which add to the shared ones.
+    pub rename: Option<Renames>,
+}
+
+impl OptionsConfig {
+    /// Returns these options with the missing ones taken from `base`.
+    pub fn or(&self, base: &OptionsConfig) -> OptionsConfig {
+        OptionsConfig {
+            template_literals: self.template_literals.or(base.template_literals),
+            type_guards: self.type_guards.or(base.type_guards),
+            brand: self.brand.clone().or_else(|| base.brand.clone()),
+            brand_all: self.brand_all.or(base.brand_all),
+            cbor_tags: self.cbor_tags.or(base.cbor_tags),
+            profile: self.profile.or(base.profile),
+            monomorphize: self.monomorphize.or(base.monomorphize),
+            extensibility: self.extensibility.or(base.extensibility),
+            enums: self.enums.or(base.enums),
+            vector_groups: self.vector_groups.or(base.vector_groups),
+            indent_width: self.indent_width.or(base.indent_width),
+            line_width: self.line_width.or(base.line_width),
+            trailing_commas: self.trailing_commas.or(base.trailing_commas),
+            quotes: self.quotes.or(base.quotes),
+            rename: match (&base.rename, &self.rename) {
+                (Some(base), Some(renames)) => {
+                    let mut base = base.clone();
+                    base.extend(renames.clone());
+                    Some(base)
+                }
+                (base, renames) => renames.clone().or_else(|| base.clone()),
+            },
+        }
+    }
+
+    /// Returns the options, with the missing ones set to their defaults.
+    pub fn to_options(&self) -> Options {
+        let layout = Layout::default();
+        Options {
+            template_literals: self.template_literals.unwrap_or_default(),
+            type_guards: self.type_guards.unwrap_or_default(),
+            brands: match (&self.brand, self.brand_all) {
+                (_, Some(true)) => Brands::All,
+                (Some(names), _) if !names.is_empty() => Brands::Only(names.clone()),
+                _ => Brands::None,
+            },
+            cbor_tags: self.cbor_tags.unwrap_or_default(),
+            profile: self.profile.unwrap_or_default(),
+            monomorphize: self.monomorphize.unwrap_or_default(),
+            extensibility: self.extensibility.unwrap_or_default(),
+            enums: self.enums.unwrap_or_default(),
+            vector_groups: self.vector_groups.unwrap_or_default(),
+            layout: Layout {
+                indent_width: self.indent_width.unwrap_or(layout.indent_width),
+                line_width: self.line_width.unwrap_or(layout.line_width),
+                trailing_commas: self.trailing_commas.unwrap_or(layout.trailing_commas),
+                quotes: self.quotes.unwrap_or(layout.quotes),
+            },
+            renames: self.rename.clone().unwrap_or_default(),
+        }
+    }
+}
+
+/// An input with everything generated from it.
+#[derive(Debug, Clone)]
+pub struct Conversion {
+    pub input: PathBuf,
+    pub outputs: Vec<Output>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Output {
+    pub format: String,
+    /// File to write to, or standard output if missing.
+    pub path: Option<PathBuf>,
+    pub options: Options,
+}
+
+impl FromStr for Config {
+    type Err = toml::de::Error;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        toml::from_str(text)
+    }
+}
+
+impl Config {
+    /// Reads a configuration file. Relative paths in it are relative to the
+    /// directory of the file.
+    pub fn load(path: &Path) -> Result<Config> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        let config = Config::from_str(&text)
+            .with_context(|| format!("failed to parse {}", path.display()))?;
+        Ok(config.relative_to(path.parent().unwrap_or(Path::new(""))))
+    }
+
+    /// Returns the configuration with its relative paths made relative to
+    /// `dir` instead.
+    pub fn relative_to(mut self, dir: &Path) -> Config {
+        let join = |path: &mut Option<PathBuf>| {
+            if let Some(path) = path {
+                *path = dir.join(&*path);
+            }
+        };
+        join(&mut self.input);
+        for target in &mut self.targets {
+            join(&mut target.input);
+            join(&mut target.output);
+        }
+        self
+    }
+
+    /// Groups the targets by input, in the order of their first target,
+    /// with their options resolved.
+    pub fn conversions(&self) -> Result<Vec<Conversion>> {
+        let mut conversions: Vec<Conversion> = Vec::new();
+        for (index, target) in self.targets.iter().enumerate() {
+            let input = target
+                .input
+                .as_ref()
+                .or(self.input.as_ref())
+                .ok_or_else(|| anyhow!("target {} has no input", index + 1))?;
+            let output = Output {
+                format: target.format.clone(),
+                path: target.output.clone(),
+                options: target.options.or(&self.options).to_options(),
+            };
+            match conversions
+                .iter_mut()
+                .find(|conversion| &conversion.input == input)
+            {
+                Some(conversion) => conversion.outputs.push(output),
+                None => conversions.push(Conversion {
+                    input: input.clone(),
+                    outputs: vec![output],
+                }),
+            }
+        }
+        Ok(conversions)
+    }
+}
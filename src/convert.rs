@@ -0,0 +1,98 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Conversion of CDDL text to text, for embedding `cddlconv` in other tools.
+//!
+//! ```
+//! use cddlconv::{convert, options::Options, Target};
+//!
+//! let output = convert("id = text", Target::TypeScript, &Options::default()).unwrap();
+//! assert_eq!(output.text, "export type Id = (string);\n");
+//! ```
+
+use anyhow::{anyhow, Result};
+use cddl::{
+    ast::CDDL,
+    lexer::Lexer,
+    parser::{Error, Parser},
+};
+
+use crate::{
+    engines::{rust::Rust, typescript::TypeScript, zod::Zod},
+    generator::{Diagnostic, Generator},
+    options::Options,
+};
+
+/// A built-in output format.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Target {
+    TypeScript,
+    Zod,
+    Rust,
+}
+
+impl Target {
+    /// The [`Generator`] of the format.
+    pub fn generator(self) -> &'static dyn Generator {
+        match self {
+            Target::TypeScript => &TypeScript,
+            Target::Zod => &Zod,
+            Target::Rust => &Rust,
+        }
+    }
+
+    /// The file extension of the format, e.g. `ts`.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Target::TypeScript | Target::Zod => "ts",
+            Target::Rust => "rs",
+        }
+    }
+}
+
+/// The result of a conversion.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Output {
+    /// Generated code.
+    pub text: String,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// Converts the CDDL `source` to `target`.
+pub fn convert(source: &str, target: Target, options: &Options) -> Result<Output> {
+    let cddl = parse(source)?;
+    let mut text = Vec::new();
+    let diagnostics = target
+        .generator()
+        .generate(&cddl, source, options, &mut text)?;
+    Ok(Output {
+        text: String::from_utf8(text)?,
+        diagnostics,
+    })
+}
+
+/// Parses CDDL text. Unlike `cddl::parser::cddl_from_str`, this returns the
+/// AST on every target, WebAssembly included.
+pub fn parse(source: &str) -> Result<CDDL<'_>> {
+    let mut parser = Parser::new(source, Box::new(Lexer::new(source).iter()))
+        .map_err(|error| anyhow!(error.to_string()))?;
+    match parser.parse_cddl() {
+        Ok(cddl) => Ok(cddl),
+        Err(Error::INCREMENTAL) => match parser.report_errors(false) {
+            Ok(Some(report)) => Err(anyhow!(report)),
+            _ => Err(anyhow!(Error::INCREMENTAL.to_string())),
+        },
+        Err(error) => Err(anyhow!(error.to_string())),
+    }
+}
@@ -0,0 +1,230 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Documentation of rules and members, taken from the comments around them.
+//!
+//! The parser attaches comments to whatever precedes them, so a comment above
+//! a member ends up after the comma of the previous one. Instead, comments are
+//! read from the source text and matched with the spans of the AST: the
+//! comment lines right above a rule or member, followed by a comment on the
+//! line it ends on.
+//!
+//! Rules the parser had to parse again (e.g. because they use a rule defined
+//! further down) have spans relative to their own start, which is found from
+//! their name.
+//!
+//! ```cddl
+//! ; A person.
+//! person = {
+//!   name: text, ; The full name.
+//! }
+//! ```
+
+use cddl::ast::{
+    GroupEntry, GroupRule, Identifier, MemberKey, Span, TypeRule, ValueMemberKeyEntry,
+};
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Line {
+    /// Offset of the line in the source.
+    start: usize,
+    /// Offset of the `;` starting a comment in the line, if any.
+    comment: Option<usize>,
+    /// Whether the line holds nothing but a comment.
+    is_comment: bool,
+}
+
+/// Comments of a source text, by line.
+#[derive(Debug, Clone, Default)]
+pub struct Docs {
+    source: String,
+    lines: Vec<Line>,
+    /// Offset and line of the start of the rule being documented, if its
+    /// spans are relative to it.
+    origin: (usize, usize),
+}
+
+impl Docs {
+    pub fn new(source: &str) -> Self {
+        let mut lines = Vec::new();
+        let mut start = 0;
+        for text in source.split_inclusive('\n') {
+            let comment = comment_offset(text);
+            lines.push(Line {
+                start,
+                comment: comment.map(|offset| start + offset),
+                is_comment: comment.is_some_and(|offset| text[..offset].trim().is_empty()),
+            });
+            start += text.len();
+        }
+        Docs {
+            source: source.to_string(),
+            lines,
+            origin: (0, 0),
+        }
+    }
+
+    /// Sets the rule whose rule and members are documented next.
+    pub fn enter_rule(&mut self, name: &Identifier) {
+        self.origin = (0, 0);
+        if name.span.2 == 0 || self.is_at(name.span, name.ident) {
+            return;
+        }
+        let definition = self.lines.iter().enumerate().find_map(|(index, line)| {
+            let text = &self.source[line.start..];
+            let sigils = text.len() - text.trim_start_matches('$').len();
+            text[sigils..]
+                .strip_prefix(name.ident)
+                .filter(|rest| {
+                    rest.starts_with(|ch: char| ch.is_whitespace() || "=</".contains(ch))
+                })
+                .map(|_| (line.start + sigils, index))
+        });
+        if let Some((offset, line)) = definition {
+            self.origin = (
+                offset.saturating_sub(name.span.0),
+                (line + 1).saturating_sub(name.span.2),
+            );
+        }
+    }
+
+    pub fn type_rule(&self, tr: &TypeRule) -> Option<String> {
+        self.get(tr.name.span, tr.value.span)
+    }
+
+    pub fn group_rule(&self, gr: &GroupRule) -> Option<String> {
+        self.get(gr.name.span, group_entry_span(&gr.entry))
+    }
+
+    pub fn member(&self, entry: &ValueMemberKeyEntry) -> Option<String> {
+        let start = match &entry.member_key {
+            Some(
+                MemberKey::Bareword { span, .. }
+                | MemberKey::Type1 { span, .. }
+                | MemberKey::Value { span, .. },
+            ) => *span,
+            _ => entry.entry_type.span,
+        };
+        self.get(start, entry.entry_type.span)
+    }
+
+    /// The documentation of an item from `start` to `end`, with its lines
+    /// separated by `\n`.
+    fn get(&self, start: Span, end: Span) -> Option<String> {
+        // Items made up by rewriting the AST have no span.
+        if start.2 == 0 || end.2 == 0 {
+            return None;
+        }
+        let (start, end) = (self.absolute(start), self.absolute(end));
+        if self.lines.is_empty()
+            || end.1 > self.source.len()
+            || self.line_of(start.0) != start.2 - 1
+        {
+            return None;
+        }
+        // Comments above a line are about the item it starts with, possibly
+        // after an occurrence indicator.
+        let starts_line = self.source[self.lines[start.2 - 1].start..start.0]
+            .chars()
+            .all(|ch| ch.is_whitespace() || ch.is_ascii_digit() || "?*+".contains(ch));
+        let mut doc = self.lines[..start.2 - 1]
+            .iter()
+            .rev()
+            .take_while(|line| starts_line && line.is_comment)
+            .map(|line| self.text(line))
+            .collect::<Vec<_>>();
+        doc.reverse();
+        let end_line = &self.lines[self.line_of(end.1)];
+        if end_line.comment.is_some_and(|comment| {
+            !end_line.is_comment
+                && comment >= end.1
+                && self.source[end.1..comment]
+                    .chars()
+                    .all(|ch| ch.is_whitespace() || ch == ',')
+        }) {
+            doc.push(self.text(end_line));
+        }
+        // Drop separators such as `;;;;` or an empty `;` around the text.
+        while doc.first().is_some_and(|line| is_separator(line)) {
+            doc.remove(0);
+        }
+        while doc.last().is_some_and(|line| is_separator(line)) {
+            doc.pop();
+        }
+        (!doc.is_empty()).then(|| doc.join("\n"))
+    }
+
+    fn absolute(&self, span: Span) -> Span {
+        (
+            span.0 + self.origin.0,
+            span.1 + self.origin.0,
+            span.2 + self.origin.1,
+        )
+    }
+
+    /// The index of the line an offset is in.
+    fn line_of(&self, offset: usize) -> usize {
+        self.lines
+            .partition_point(|line| line.start <= offset)
+            .saturating_sub(1)
+    }
+
+    fn is_at(&self, span: Span, ident: &str) -> bool {
+        self.source
+            .get(span.0..span.1)
+            .is_some_and(|text| text.trim_start_matches('$') == ident)
+            && self.line_of(span.0) + 1 == span.2
+    }
+
+    /// The text of the comment of a line.
+    fn text(&self, line: &Line) -> &str {
+        let start = line.comment.unwrap() + 1;
+        let end = self.source[start..]
+            .find('\n')
+            .map_or(self.source.len(), |offset| start + offset);
+        let text = self.source[start..end].trim_end();
+        text.strip_prefix(' ').unwrap_or(text)
+    }
+}
+
+/// The offset of the `;` starting a comment in a line, skipping text and
+/// byte strings.
+fn comment_offset(line: &str) -> Option<usize> {
+    let mut quote = None;
+    let mut escaped = false;
+    for (offset, ch) in line.char_indices() {
+        match quote {
+            Some(_) if escaped => escaped = false,
+            Some(_) if ch == '\\' => escaped = true,
+            Some(q) if ch == q => quote = None,
+            Some(_) => {}
+            None if ch == '"' || ch == '\'' => quote = Some(ch),
+            None if ch == ';' => return Some(offset),
+            None => {}
+        }
+    }
+    None
+}
+
+fn is_separator(line: &str) -> bool {
+    line.chars().all(|ch| ch == ';' || ch.is_whitespace())
+}
+
+fn group_entry_span(entry: &GroupEntry) -> Span {
+    match entry {
+        GroupEntry::ValueMemberKey { span, .. }
+        | GroupEntry::TypeGroupname { span, .. }
+        | GroupEntry::InlineGroup { span, .. } => *span,
+    }
+}
@@ -0,0 +1,1227 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Rust types that (de)serialize with `serde`.
+//!
+//! Maps become structs, choices become enums and arrays become vectors or
+//! tuples. Maps and choices nested in a rule get a definition of their own,
+//! named after the rule and the member they appear in. Literal values are unit
+//! structs that only accept that value, which tells the variants of untagged
+//! enums apart. Other constraints are not checked: control operators and
+//! ranges become the type they constrain. `any`, and sockets without plugs, are
+//! a `serde_json::Value`, or a `ciborium::Value` in the CBOR profile.
+//! Constructs without a Rust equivalent (e.g. integer keys) are reported.
+
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    io::Write,
+};
+
+use cddl::{
+    ast::{
+        Group, GroupChoice, GroupEntry, MemberKey, Occur, Occurrence, Operator, RangeCtlOp, Rule,
+        Type, Type1, Type2, ValueMemberKeyEntry, CDDL,
+    },
+    token::{ControlOperator, Value},
+};
+use convert_case::{Case, Casing};
+
+use crate::{
+    amendments::merge_amendments,
+    docs::Docs,
+    generator::{diagnostics, with_instances, Diagnostic, Generator},
+    naming::{to_words, Names},
+    options::{Options, Profile},
+    unwrap::unwrap,
+    util::{major_type_prelude, unescape_text, well_known_tag, WellKnownTag},
+};
+
+/// Types of the standard library referenced by the output, which rules must
+/// not shadow.
+const RESERVED: &[&str] = &[
+    "Box", "Err", "From", "Ok", "Option", "Result", "Self", "String", "TryFrom", "Vec",
+];
+
+/// Keywords of Rust, which fields are raw identifiers (e.g. `r#type`) of.
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "dyn", "else", "enum", "extern", "false",
+    "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref",
+    "return", "static", "struct", "trait", "true", "type", "unsafe", "use", "where", "while",
+    "abstract", "become", "box", "do", "final", "gen", "macro", "override", "priv", "try",
+    "typeof", "unsized", "virtual", "yield",
+];
+
+const DERIVE: &str = "#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]";
+
+/// The [`Generator`] of Rust types.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Rust;
+
+impl Generator for Rust {
+    /// Generic rules are always instantiated, as if `monomorphize` was set.
+    fn generate(
+        &self,
+        cddl: &CDDL,
+        source: &str,
+        options: &Options,
+        out: &mut dyn Write,
+    ) -> anyhow::Result<Vec<Diagnostic>> {
+        let instances = Options {
+            monomorphize: true,
+            ..options.clone()
+        };
+        with_instances(cddl, &instances, |cddl| {
+            let mut stderr = Vec::new();
+            let cddl = merge_amendments(cddl);
+            let (cddl, unwraps) = unwrap(&cddl);
+            for unwrap in unwraps {
+                writeln!(stderr, "{}", unwrap)?;
+            }
+            let (names, collisions) = Names::new(&cddl, &options.renames, RESERVED, |_| Vec::new());
+            for collision in collisions {
+                writeln!(stderr, "{}", collision)?;
+            }
+            let mut engine = Engine::new(&cddl, options, names, Docs::new(source));
+            for rule in &cddl.rules {
+                engine.rule(rule);
+            }
+            for diagnostic in &engine.diagnostics {
+                writeln!(stderr, "{}", diagnostic)?;
+            }
+            out.write_all(engine.items.join("\n").as_bytes())?;
+            out.flush()?;
+            Ok(diagnostics(&stderr))
+        })
+    }
+}
+
+/// A field of a struct.
+struct Field {
+    doc: Option<String>,
+    /// Key the field is serialized as, if it is not flattened.
+    key: Option<String>,
+    name: String,
+    ty: String,
+    optional: bool,
+}
+
+struct Engine<'c, 'a> {
+    options: &'c Options,
+    names: Names,
+    docs: Docs,
+    /// Rules by name.
+    rules: HashMap<&'a str, &'c Rule<'a>>,
+    /// Rules referenced by each rule, directly or not.
+    reachable: HashMap<&'a str, HashSet<&'a str>>,
+    /// CDDL name of the rule being emitted.
+    rule: &'a str,
+    /// Names of the emitted definitions.
+    taken: HashSet<String>,
+    /// Definitions, in order.
+    items: Vec<String>,
+    /// Names of the defined literal types, keyed by type and value.
+    literals: HashMap<(&'static str, String), String>,
+    diagnostics: Vec<String>,
+}
+
+impl<'c, 'a> Engine<'c, 'a> {
+    fn new(cddl: &'c CDDL<'a>, options: &'c Options, names: Names, docs: Docs) -> Self {
+        let rules = cddl
+            .rules
+            .iter()
+            .map(|rule| (rule_name(rule), rule))
+            .collect::<HashMap<_, _>>();
+        let references = rules
+            .iter()
+            .map(|(name, rule)| {
+                let mut references = BTreeSet::new();
+                match rule {
+                    Rule::Type { rule, .. } => type_references(&rule.value, &mut references),
+                    Rule::Group { rule, .. } => entry_references(&rule.entry, &mut references),
+                }
+                (*name, references)
+            })
+            .collect::<HashMap<_, _>>();
+        let reachable = references
+            .keys()
+            .map(|name| {
+                let mut reachable = HashSet::new();
+                let mut pending = vec![*name];
+                while let Some(name) = pending.pop() {
+                    for reference in references.get(name).into_iter().flatten() {
+                        if reachable.insert(*reference) {
+                            pending.push(reference);
+                        }
+                    }
+                }
+                (*name, reachable)
+            })
+            .collect();
+        let taken = rules
+            .keys()
+            .map(|name| names.rule(name).replace(['.', '$'], ""))
+            .collect();
+        Engine {
+            options,
+            names,
+            docs,
+            rules,
+            reachable,
+            rule: "",
+            taken,
+            items: Vec::new(),
+            literals: HashMap::new(),
+            diagnostics: Vec::new(),
+        }
+    }
+
+    fn report(&mut self, diagnostic: String) {
+        if !self.diagnostics.contains(&diagnostic) {
+            self.diagnostics.push(diagnostic);
+        }
+    }
+
+    fn type_name(&self, ident: &str) -> String {
+        self.names.rule(ident).replace(['.', '$'], "")
+    }
+
+    /// A name for a nested definition that no other definition has.
+    fn nested_name(&mut self, name: String) -> String {
+        // Underscores of reserved names (e.g. `Result_`) end up in between.
+        let unprefixed = name.trim_start_matches('_');
+        let name = format!(
+            "{}{}",
+            &name[..name.len() - unprefixed.len()],
+            unprefixed.replace('_', "")
+        );
+        let mut unique = name.clone();
+        let mut number = 1;
+        while self.taken.contains(&unique) {
+            number += 1;
+            unique = format!("{}{}", name, number);
+        }
+        self.taken.insert(unique.clone());
+        unique
+    }
+
+    fn rule(&mut self, rule: &'c Rule<'a>) {
+        self.rule = rule_name(rule);
+        match rule {
+            Rule::Type { rule, .. } => {
+                self.docs.enter_rule(&rule.name);
+                let doc = self.docs.type_rule(rule);
+                let name = self.type_name(rule.name.ident);
+                self.type_definition(doc, name, &rule.value);
+            }
+            Rule::Group { rule, .. } => {
+                self.docs.enter_rule(&rule.name);
+                let doc = self.docs.group_rule(rule);
+                let name = self.type_name(rule.name.ident);
+                match self.type_entry(&rule.entry) {
+                    Some(GroupEntry::ValueMemberKey { ge, .. }) => {
+                        self.type_definition(doc, name, &ge.entry_type);
+                        return;
+                    }
+                    Some(GroupEntry::TypeGroupname { ge, .. }) => {
+                        let ty = self
+                            .prelude_type(ge.name.ident)
+                            .unwrap_or_else(|| self.reference(ge.name.ident, true));
+                        self.items.push(format!(
+                            "{}pub type {} = {};\n",
+                            doc_comment(&doc, ""),
+                            name,
+                            ty
+                        ));
+                        return;
+                    }
+                    _ => {}
+                }
+                let choices = match &rule.entry {
+                    GroupEntry::InlineGroup {
+                        occur: None, group, ..
+                    } => group.group_choices.iter().collect(),
+                    entry => {
+                        let index = self.items.len();
+                        let fields = self.fields(&name, [entry]);
+                        self.struct_definition(index, doc, &name, fields);
+                        return;
+                    }
+                };
+                self.group_definition(doc, &name, choices);
+            }
+        }
+    }
+
+    /// The entry of a group rule made of a single type (e.g. `result = (
+    /// success / failure )`), which stands for that type.
+    fn type_entry<'e>(&self, entry: &'e GroupEntry<'a>) -> Option<&'e GroupEntry<'a>> {
+        let is_type = |ident| !matches!(self.rules.get(ident), Some(Rule::Group { .. }));
+        match entry {
+            GroupEntry::ValueMemberKey { ge, .. }
+                if ge.occur.is_none()
+                    && ge.member_key.is_none()
+                    && group_reference(ge).is_none_or(is_type) =>
+            {
+                Some(entry)
+            }
+            GroupEntry::TypeGroupname { ge, .. }
+                if ge.occur.is_none() && ge.name.socket.is_none() && is_type(ge.name.ident) =>
+            {
+                Some(entry)
+            }
+            GroupEntry::InlineGroup {
+                occur: None, group, ..
+            } => match group.group_choices.as_slice() {
+                [choice] => match choice.group_entries.as_slice() {
+                    [(entry, _)] => self.type_entry(entry),
+                    _ => None,
+                },
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Defines `name` as `t`: a struct, an enum or an alias.
+    fn type_definition(&mut self, doc: Option<String>, name: String, t: &Type<'a>) {
+        if let [choice] = t.type_choices.as_slice() {
+            match (&choice.type1.type2, &choice.type1.operator) {
+                (Type2::Map { group, .. }, None) if !is_record(group) => {
+                    self.group_definition(doc, &name, group.group_choices.iter().collect());
+                    return;
+                }
+                // The members of both maps, e.g. for `{ a: uint } .and other`.
+                (
+                    Type2::Map { group, .. },
+                    Some(Operator {
+                        operator:
+                            RangeCtlOp::CtlOp {
+                                ctrl: ControlOperator::AND,
+                                ..
+                            },
+                        type2: Type2::Typename { ident, .. },
+                        ..
+                    }),
+                ) if !is_record(group) => {
+                    if let [choice] = group.group_choices.as_slice() {
+                        let index = self.items.len();
+                        let entries = choice.group_entries.iter().map(|(entry, _)| entry);
+                        let mut fields = self.fields(&name, entries);
+                        fields.push(Field {
+                            doc: None,
+                            key: None,
+                            name: field_identifier(ident.ident),
+                            ty: self.reference(ident.ident, true),
+                            optional: false,
+                        });
+                        self.struct_definition(index, doc, &name, fields);
+                        return;
+                    }
+                }
+                (Type2::ParenthesizedType { pt, .. }, None) => {
+                    self.type_definition(doc, name, pt);
+                    return;
+                }
+                (t2, None) if literal(t2).is_some() => {
+                    let (ty, value) = literal(t2).unwrap();
+                    self.literals.insert((ty, value.clone()), name.clone());
+                    self.literal_definition(doc, &name, ty, &value);
+                    return;
+                }
+                _ => {}
+            }
+        }
+        if let Some(values) = text_values(t) {
+            self.text_enum(doc, &name, values);
+            return;
+        }
+        if t.type_choices.len() > 1 && nullable(t).is_none() && self.uniform_type(t).is_none() {
+            self.choice_enum(doc, &name, t);
+            return;
+        }
+        let index = self.items.len();
+        let ty = self.rust_type(&name, t, true);
+        self.items.insert(
+            index,
+            format!("{}pub type {} = {};\n", doc_comment(&doc, ""), name, ty),
+        );
+    }
+
+    /// Defines `name` as a struct, or as an enum of structs for several
+    /// group choices.
+    fn group_definition(
+        &mut self,
+        doc: Option<String>,
+        name: &str,
+        choices: Vec<&GroupChoice<'a>>,
+    ) {
+        let index = self.items.len();
+        if let [choice] = choices.as_slice() {
+            let fields = self.fields(name, choice.group_entries.iter().map(|(entry, _)| entry));
+            self.struct_definition(index, doc, name, fields);
+            return;
+        }
+        let mut variants = Vec::new();
+        for (number, choice) in choices.iter().enumerate() {
+            let variant = format!("Variant{}", number + 1);
+            let variant_name = self.nested_name(format!("{}{}", name, variant));
+            let variant_index = self.items.len();
+            let fields = self.fields(
+                &variant_name,
+                choice.group_entries.iter().map(|(entry, _)| entry),
+            );
+            self.struct_definition(variant_index, None, &variant_name, fields);
+            variants.push((variant, variant_name));
+        }
+        let mut item = format!(
+            "{}{}\n#[serde(untagged)]\npub enum {} {{\n",
+            doc_comment(&doc, ""),
+            DERIVE,
+            name
+        );
+        for (variant, ty) in variants {
+            item.push_str(&format!("    {}({}),\n", variant, ty));
+        }
+        item.push_str("}\n");
+        self.items.insert(index, item);
+    }
+
+    /// Defines the struct `name`, before the definitions from `index` on that
+    /// its fields needed.
+    fn struct_definition(
+        &mut self,
+        index: usize,
+        doc: Option<String>,
+        name: &str,
+        fields: Vec<Field>,
+    ) {
+        let mut item = format!(
+            "{}{}\npub struct {} {{\n",
+            doc_comment(&doc, ""),
+            DERIVE,
+            name
+        );
+        for field in fields {
+            item.push_str(&doc_comment(&field.doc, "    "));
+            match (&field.key, field.optional) {
+                (None, _) => item.push_str("    #[serde(flatten)]\n"),
+                (Some(key), optional) => {
+                    let mut attributes = Vec::new();
+                    if raw_name(&field.name) != key {
+                        attributes.push(format!("rename = {:?}", key));
+                    }
+                    if optional {
+                        attributes.push("default".to_string());
+                        attributes.push("skip_serializing_if = \"Option::is_none\"".to_string());
+                    }
+                    if !attributes.is_empty() {
+                        item.push_str(&format!("    #[serde({})]\n", attributes.join(", ")));
+                    }
+                }
+            }
+            // `? label: text / null` is already an `Option`.
+            let ty = match field.optional && !field.ty.starts_with("Option<") {
+                true => format!("Option<{}>", field.ty),
+                false => field.ty,
+            };
+            item.push_str(&format!("    pub {}: {},\n", field.name, ty));
+        }
+        item.push_str("}\n");
+        self.items.insert(index, item);
+    }
+
+    /// An enum of the text values of a choice (e.g. `"red" / "blue"`).
+    fn text_enum(&mut self, doc: Option<String>, name: &str, values: Vec<String>) {
+        let mut variants = Vec::<String>::new();
+        for (number, value) in values.iter().enumerate() {
+            let name = type_identifier(value).unwrap_or_else(|| format!("Variant{}", number + 1));
+            let mut unique = name.clone();
+            let mut number = 1;
+            while variants.contains(&unique) {
+                number += 1;
+                unique = format!("{}{}", name, number);
+            }
+            if let Some(owner) = variants.iter().position(|variant| *variant == name) {
+                self.report(format!(
+                    "In `{}`, `{}` is named `{}` since `{}` is the name of `{}`",
+                    self.rule, value, unique, name, values[owner]
+                ));
+            }
+            variants.push(unique);
+        }
+        let mut item = format!(
+            "{}{}\npub enum {} {{\n",
+            doc_comment(&doc, ""),
+            DERIVE,
+            name
+        );
+        for (value, variant) in values.iter().zip(variants) {
+            if variant != *value {
+                item.push_str(&format!("    #[serde(rename = {:?})]\n", value));
+            }
+            item.push_str(&format!("    {},\n", variant));
+        }
+        item.push_str("}\n");
+        self.items.push(item);
+    }
+
+    /// An untagged enum of the choices of `t`, which are told apart by their
+    /// content.
+    fn choice_enum(&mut self, doc: Option<String>, name: &str, t: &Type<'a>) {
+        let index = self.items.len();
+        let mut variants = Vec::<(String, String)>::new();
+        for (number, choice) in t.type_choices.iter().enumerate() {
+            let mut variant = match (&choice.type1.type2, &choice.type1.operator) {
+                (Type2::Typename { ident, .. }, _) => self.type_name(ident.ident),
+                _ => format!("Variant{}", number + 1),
+            };
+            if variants.iter().any(|(other, _)| *other == variant) {
+                variant = format!("{}{}", variant, number + 1);
+            }
+            let ty = self.type1_type(&format!("{}{}", name, variant), &choice.type1, true);
+            variants.push((variant, ty));
+        }
+        let mut item = format!(
+            "{}{}\n#[serde(untagged)]\npub enum {} {{\n",
+            doc_comment(&doc, ""),
+            DERIVE,
+            name
+        );
+        for (variant, ty) in variants {
+            item.push_str(&format!("    {}({}),\n", variant, ty));
+        }
+        item.push_str("}\n");
+        self.items.insert(index, item);
+    }
+
+    /// The fields of the members of a group choice.
+    fn fields<'e>(
+        &mut self,
+        name: &str,
+        entries: impl IntoIterator<Item = &'e GroupEntry<'a>>,
+    ) -> Vec<Field>
+    where
+        'a: 'e,
+    {
+        let mut fields = Vec::new();
+        for entry in entries {
+            self.entry_fields(name, entry, &mut fields);
+        }
+        let mut seen = HashSet::new();
+        for field in &mut fields {
+            let mut unique = field.name.clone();
+            let mut number = 1;
+            while !seen.insert(unique.clone()) {
+                number += 1;
+                unique = format!("{}{}", raw_name(&field.name), number);
+            }
+            field.name = unique;
+        }
+        fields
+    }
+
+    fn entry_fields(&mut self, name: &str, entry: &GroupEntry<'a>, fields: &mut Vec<Field>) {
+        match entry {
+            GroupEntry::ValueMemberKey { ge, .. } => match &ge.member_key {
+                Some(mk @ (MemberKey::Bareword { .. } | MemberKey::Value { .. })) => {
+                    let key = match mk {
+                        MemberKey::Bareword { ident, .. } => ident.ident.to_string(),
+                        MemberKey::Value {
+                            value: Value::TEXT(value),
+                            ..
+                        } => unescape_text(value),
+                        _ => {
+                            self.report(format!(
+                                "In `{}`, the member `{}` has a key that is not text, which \
+                                Rust types cannot have; it is left out.",
+                                self.rule, mk
+                            ));
+                            return;
+                        }
+                    };
+                    let field_name = self
+                        .options
+                        .renames
+                        .property(self.rule, &key)
+                        .unwrap_or(&key)
+                        .to_string();
+                    let ty = self.rust_type(
+                        &format!(
+                            "{}{}",
+                            name,
+                            type_identifier(&field_name).unwrap_or_default()
+                        ),
+                        &ge.entry_type,
+                        true,
+                    );
+                    fields.push(Field {
+                        doc: self.docs.member(ge),
+                        key: Some(key),
+                        name: field_identifier(&field_name),
+                        ty,
+                        optional: is_optional(&ge.occur),
+                    });
+                }
+                Some(MemberKey::Type1 { t1, .. }) => {
+                    let key = self.type1_type(name, t1, false);
+                    let value = self.rust_type(&format!("{}Value", name), &ge.entry_type, false);
+                    fields.push(Field {
+                        doc: self.docs.member(ge),
+                        key: None,
+                        name: "extra".to_string(),
+                        ty: format!("std::collections::BTreeMap<{}, {}>", key, value),
+                        optional: false,
+                    });
+                }
+                // Bare group names may parse as members without a key.
+                None => match group_reference(ge) {
+                    Some(ident) => self.group_field(ident, &ge.occur, fields),
+                    None => self.report(format!(
+                        "In `{}`, `{}` has no key; it is left out.",
+                        self.rule, ge.entry_type
+                    )),
+                },
+                Some(MemberKey::NonMemberKey { .. }) => {}
+            },
+            // Group sockets without plugs accept any other member.
+            GroupEntry::TypeGroupname { ge, .. } => match ge.name.socket {
+                Some(_) if !self.rules.contains_key(ge.name.ident) => fields.push(Field {
+                    doc: None,
+                    key: None,
+                    name: field_identifier(ge.name.ident),
+                    ty: format!("std::collections::BTreeMap<String, {}>", self.any_type()),
+                    optional: false,
+                }),
+                _ => self.group_field(ge.name.ident, &ge.occur, fields),
+            },
+            GroupEntry::InlineGroup { occur, group, .. } => match group.group_choices.as_slice() {
+                [choice] if !is_optional(occur) => {
+                    for (entry, _) in &choice.group_entries {
+                        self.entry_fields(name, entry, fields);
+                    }
+                }
+                choices => {
+                    let group_name = self.nested_name(format!("{}Group", name));
+                    self.group_definition(None, &group_name, choices.iter().collect());
+                    fields.push(Field {
+                        doc: None,
+                        key: None,
+                        name: "group".to_string(),
+                        ty: group_name,
+                        optional: is_optional(occur),
+                    });
+                }
+            },
+        }
+    }
+
+    /// A flattened field of the group rule `ident`.
+    fn group_field(&mut self, ident: &'a str, occur: &Option<Occurrence>, fields: &mut Vec<Field>) {
+        match self.rules.get(ident) {
+            Some(Rule::Group { .. }) => fields.push(Field {
+                doc: None,
+                key: None,
+                name: field_identifier(ident),
+                ty: self.reference(ident, true),
+                optional: is_optional(occur),
+            }),
+            _ => self.report(format!(
+                "In `{}`, `{}` is not a group; it is left out.",
+                self.rule, ident
+            )),
+        }
+    }
+
+    /// The type of `t`, whose nested definitions are named after `name`.
+    /// `direct` is whether a value of the type is stored in place, as opposed
+    /// to in a collection, which recursive types must not be.
+    fn rust_type(&mut self, name: &str, t: &Type<'a>, direct: bool) -> String {
+        if let Some(t1) = nullable(t) {
+            return format!("Option<{}>", self.type1_type(name, t1, direct));
+        }
+        match t.type_choices.as_slice() {
+            [choice] => self.type1_type(name, &choice.type1, direct),
+            _ if self.uniform_type(t).is_some() => self.uniform_type(t).unwrap(),
+            _ => {
+                let name = self.nested_name(name.to_string());
+                match text_values(t) {
+                    Some(values) => self.text_enum(None, &name, values),
+                    None => self.choice_enum(None, &name, t),
+                }
+                name
+            }
+        }
+    }
+
+    fn type1_type(&mut self, name: &str, t1: &Type1<'a>, direct: bool) -> String {
+        match &t1.operator {
+            Some(op) => match op.operator {
+                RangeCtlOp::RangeOp { .. } => range_type(&t1.type2, &op.type2).to_string(),
+                RangeCtlOp::CtlOp { ctrl, .. } => match ctrl {
+                    ControlOperator::CBOR | ControlOperator::CBORSEQ => self.bytes_type(),
+                    _ => self.type2_type(name, &t1.type2, direct),
+                },
+            },
+            None => match literal(&t1.type2) {
+                Some((ty, value)) => self.literal_type(name, ty, value),
+                None => self.type2_type(name, &t1.type2, direct),
+            },
+        }
+    }
+
+    /// A type of the single value `value` of `ty`, named after `name` unless
+    /// one was already defined.
+    fn literal_type(&mut self, name: &str, ty: &'static str, value: String) -> String {
+        if let Some(name) = self.literals.get(&(ty, value.clone())) {
+            return name.clone();
+        }
+        let name = self.nested_name(name.to_string());
+        self.literals.insert((ty, value.clone()), name.clone());
+        self.literal_definition(None, &name, ty, &value);
+        name
+    }
+
+    /// Defines `name` as a unit struct that (de)serializes as `value` and
+    /// rejects other values of `ty`, which tells apart the variants of
+    /// untagged enums.
+    fn literal_definition(&mut self, doc: Option<String>, name: &str, ty: &str, value: &str) {
+        let matches = match (ty, value) {
+            ("bool", "true") => "value".to_string(),
+            ("bool", _) => "!value".to_string(),
+            _ => format!("value == {}", value),
+        };
+        let into = match ty {
+            "String" => format!("{}.to_string()", value),
+            _ => value.to_string(),
+        };
+        self.items.push(format!(
+            "{doc}#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(try_from = \"{ty}\", into = \"{ty}\")]
+pub struct {name};
+
+impl TryFrom<{ty}> for {name} {{
+    type Error = String;
+
+    fn try_from(value: {ty}) -> Result<Self, Self::Error> {{
+        if {matches} {{
+            Ok(Self)
+        }} else {{
+            Err(format!(\"expected {escaped}, found {{:?}}\", value))
+        }}
+    }}
+}}
+
+impl From<{name}> for {ty} {{
+    fn from(_: {name}) -> Self {{
+        {into}
+    }}
+}}
+",
+            doc = doc_comment(&doc, ""),
+            escaped = value
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('{', "{{")
+                .replace('}', "}}"),
+        ));
+    }
+
+    fn type2_type(&mut self, name: &str, t2: &Type2<'a>, direct: bool) -> String {
+        match t2 {
+            Type2::IntValue { .. } => "i64".to_string(),
+            Type2::UintValue { .. } => "u64".to_string(),
+            Type2::FloatValue { .. } => "f64".to_string(),
+            Type2::TextValue { .. } => "String".to_string(),
+            Type2::UTF8ByteString { .. }
+            | Type2::B16ByteString { .. }
+            | Type2::B64ByteString { .. } => self.bytes_type(),
+            Type2::Typename { ident, .. } | Type2::Unwrap { ident, .. } => {
+                // Sockets without plugs accept any value.
+                if ident.socket.is_some() && !self.rules.contains_key(ident.ident) {
+                    return self.any_type();
+                }
+                match self.prelude_type(ident.ident) {
+                    Some(ty) => ty,
+                    None => self.reference(ident.ident, direct),
+                }
+            }
+            Type2::ParenthesizedType { pt, .. } => self.rust_type(name, pt, direct),
+            Type2::Map { group, .. } => {
+                if is_record(group) {
+                    let (entry, _) = &group.group_choices[0].group_entries[0];
+                    let GroupEntry::ValueMemberKey { ge, .. } = entry else {
+                        unreachable!()
+                    };
+                    let Some(MemberKey::Type1 { t1, .. }) = &ge.member_key else {
+                        unreachable!()
+                    };
+                    let key = self.type1_type(name, t1, false);
+                    let value = self.rust_type(&format!("{}Value", name), &ge.entry_type, false);
+                    return format!("std::collections::BTreeMap<{}, {}>", key, value);
+                }
+                let name = self.nested_name(name.to_string());
+                self.group_definition(None, &name, group.group_choices.iter().collect());
+                name
+            }
+            Type2::Array { group, .. } => self.array_type(name, group),
+            Type2::TaggedData { tag, t, .. } => {
+                let tag = tag.as_ref().and_then(|tag| tag.as_literal());
+                match well_known_tag(tag, Some(t)) {
+                    Some(WellKnownTag::Prelude(prelude)) => self.prelude_type(prelude).unwrap(),
+                    _ => self.rust_type(name, t, direct),
+                }
+            }
+            Type2::DataMajorType { mt, constraint, .. } => {
+                let constraint = constraint
+                    .as_ref()
+                    .and_then(|constraint| constraint.as_literal());
+                let prelude = match (mt, well_known_tag(constraint, None)) {
+                    (6, Some(WellKnownTag::Prelude(prelude))) => Some(prelude),
+                    _ => major_type_prelude(*mt, constraint),
+                };
+                match prelude {
+                    Some(prelude) => self.prelude_type(prelude).unwrap(),
+                    None => self.any_type(),
+                }
+            }
+            Type2::Any { .. } => self.any_type(),
+            Type2::ChoiceFromInlineGroup { .. } | Type2::ChoiceFromGroup { .. } => {
+                self.report(format!(
+                    "In `{}`, `{}` could not be resolved; it is `any`.",
+                    self.rule, t2
+                ));
+                self.any_type()
+            }
+        }
+    }
+
+    /// The type of every choice of `t`, if they all have the same one (e.g.
+    /// `u64` for `200 / 404`).
+    fn uniform_type(&self, t: &Type<'a>) -> Option<String> {
+        let mut types = t.type_choices.iter().map(|choice| {
+            let t1 = &choice.type1;
+            match (&t1.type2, &t1.operator) {
+                (
+                    lower,
+                    Some(Operator {
+                        operator: RangeCtlOp::RangeOp { .. },
+                        type2: upper,
+                        ..
+                    }),
+                ) => Some(range_type(lower, upper).to_string()),
+                (_, Some(_)) => None,
+                (Type2::IntValue { .. }, None) => Some("i64".to_string()),
+                (Type2::UintValue { .. }, None) => Some("u64".to_string()),
+                (Type2::FloatValue { .. }, None) => Some("f64".to_string()),
+                (Type2::TextValue { .. }, None) => Some("String".to_string()),
+                (Type2::Typename { ident, .. }, None) => self.prelude_type(ident.ident),
+                _ => None,
+            }
+        });
+        let first = types.next()??;
+        types.all(|ty| ty.as_ref() == Some(&first)).then_some(first)
+    }
+
+    /// Arrays of a single repeated element are vectors and arrays of fixed
+    /// elements are tuples.
+    fn array_type(&mut self, name: &str, group: &Group<'a>) -> String {
+        let [choice] = group.group_choices.as_slice() else {
+            self.report(format!(
+                "In `{}`, an array with group choices is a vector of `any`.",
+                self.rule
+            ));
+            return format!("Vec<{}>", self.any_type());
+        };
+        let mut elements = Vec::new();
+        let entries = choice
+            .group_entries
+            .iter()
+            .map(|(entry, _)| entry)
+            .collect();
+        if self.array_elements(entries, &mut Vec::new(), &mut elements) {
+            match elements.as_slice() {
+                [element] if element.occur.is_some() && !is_optional_once(&element.occur) => {
+                    let ty =
+                        self.rust_type(&format!("{}Element", name), &element.entry_type, false);
+                    return format!("Vec<{}>", ty);
+                }
+                elements if elements.iter().all(|element| element.occur.is_none()) => {
+                    let types = elements
+                        .iter()
+                        .enumerate()
+                        .map(|(index, element)| {
+                            self.rust_type(
+                                &format!("{}{}", name, index + 1),
+                                &element.entry_type,
+                                true,
+                            )
+                        })
+                        .collect::<Vec<_>>();
+                    return match types.as_slice() {
+                        [ty] => format!("({},)", ty),
+                        types => format!("({})", types.join(", ")),
+                    };
+                }
+                _ => {}
+            }
+        }
+        self.report(format!(
+            "In `{}`, an array of optional, repeated or grouped elements is a vector of `any`.",
+            self.rule
+        ));
+        format!("Vec<{}>", self.any_type())
+    }
+
+    /// Collects the elements of an array, with the entries of the groups it
+    /// includes. Returns whether every entry is an element.
+    fn array_elements(
+        &self,
+        entries: Vec<&GroupEntry<'a>>,
+        groups: &mut Vec<&'a str>,
+        elements: &mut Vec<ValueMemberKeyEntry<'a>>,
+    ) -> bool {
+        for entry in entries {
+            let included = match entry {
+                GroupEntry::ValueMemberKey { ge, .. } => match group_reference(ge) {
+                    Some(ident) if ge.occur.is_none() => Some(ident),
+                    Some(ident) if matches!(self.rules.get(ident), Some(Rule::Group { .. })) => {
+                        return false
+                    }
+                    _ => {
+                        elements.push((**ge).clone());
+                        None
+                    }
+                },
+                GroupEntry::TypeGroupname { ge, .. } if ge.occur.is_none() => Some(ge.name.ident),
+                GroupEntry::InlineGroup {
+                    occur: None, group, ..
+                } => match group.group_choices.as_slice() {
+                    [choice] => {
+                        let entries = choice
+                            .group_entries
+                            .iter()
+                            .map(|(entry, _)| entry)
+                            .collect();
+                        if !self.array_elements(entries, groups, elements) {
+                            return false;
+                        }
+                        None
+                    }
+                    _ => return false,
+                },
+                _ => return false,
+            };
+            let Some(ident) = included else {
+                continue;
+            };
+            match self.rules.get(ident) {
+                Some(Rule::Group { rule, .. }) if !groups.contains(&ident) => {
+                    groups.push(ident);
+                    let included = self.array_elements(vec![&rule.entry], groups, elements);
+                    groups.pop();
+                    if !included {
+                        return false;
+                    }
+                }
+                Some(Rule::Group { .. }) => return false,
+                _ => {
+                    let GroupEntry::ValueMemberKey { ge, .. } = entry else {
+                        return false;
+                    };
+                    elements.push((**ge).clone());
+                }
+            }
+        }
+        true
+    }
+
+    /// A reference to the rule `ident`, boxed if it is recursive and stored
+    /// in place.
+    fn reference(&self, ident: &str, direct: bool) -> String {
+        let name = self.type_name(ident);
+        let is_recursive = ident == self.rule
+            || self
+                .reachable
+                .get(ident)
+                .is_some_and(|reachable| reachable.contains(self.rule));
+        match direct && is_recursive {
+            true => format!("Box<{}>", name),
+            false => name,
+        }
+    }
+
+    fn prelude_type(&self, ident: &str) -> Option<String> {
+        let cbor = self.options.profile == Profile::Cbor;
+        Some(match ident {
+            "bool" | "true" | "false" => "bool".to_string(),
+            "uint" => "u64".to_string(),
+            "nint" | "int" => "i64".to_string(),
+            "biguint" if cbor => "u128".to_string(),
+            "bignint" | "bigint" if cbor => "i128".to_string(),
+            "biguint" => "u64".to_string(),
+            "bignint" | "bigint" => "i64".to_string(),
+            "float16" | "float32" => "f32".to_string(),
+            "float64" | "float16-32" | "float32-64" | "float" | "number" | "time" => {
+                "f64".to_string()
+            }
+            "bstr" | "bytes" | "encoded-cbor" => self.bytes_type(),
+            "tstr" | "text" | "tdate" | "uri" | "b64url" | "b64legacy" | "regexp"
+            | "mime-message" => "String".to_string(),
+            "any" | "cbor-any" => self.any_type(),
+            "nil" | "null" | "undefined" => "()".to_string(),
+            _ => return None,
+        })
+    }
+
+    fn bytes_type(&self) -> String {
+        match self.options.profile {
+            Profile::Json => "String".to_string(),
+            Profile::Cbor => "Vec<u8>".to_string(),
+        }
+    }
+
+    fn any_type(&self) -> String {
+        match self.options.profile {
+            Profile::Json => "serde_json::Value".to_string(),
+            Profile::Cbor => "ciborium::Value".to_string(),
+        }
+    }
+}
+
+/// The type and the Rust literal of a literal value, e.g. `("String",
+/// "\"success\"")` for `"success"`.
+fn literal(t2: &Type2) -> Option<(&'static str, String)> {
+    Some(match t2 {
+        Type2::TextValue { value, .. } => ("String", format!("{:?}", unescape_text(value))),
+        Type2::UintValue { value, .. } => ("u64", value.to_string()),
+        Type2::IntValue { value, .. } => ("i64", value.to_string()),
+        Type2::FloatValue { value, .. } => ("f64", format!("{:?}", value)),
+        Type2::Typename {
+            ident,
+            generic_args: None,
+            ..
+        } if ident.socket.is_none() && matches!(ident.ident, "true" | "false") => {
+            ("bool", ident.ident.to_string())
+        }
+        _ => return None,
+    })
+}
+
+fn rule_name<'a>(rule: &Rule<'a>) -> &'a str {
+    match rule {
+        Rule::Type { rule, .. } => rule.name.ident,
+        Rule::Group { rule, .. } => rule.name.ident,
+    }
+}
+
+fn doc_comment(doc: &Option<String>, indent: &str) -> String {
+    doc.iter()
+        .flat_map(|doc| doc.lines())
+        .map(|line| match line {
+            "" => format!("{}///\n", indent),
+            line => format!("{}/// {}\n", indent, line),
+        })
+        .collect()
+}
+
+/// A type or variant name for `name`, e.g. `NoCors` for `no-cors`, unless it
+/// has no letters or digits.
+fn type_identifier(name: &str) -> Option<String> {
+    let mut identifier = to_words(name);
+    if identifier.is_empty() {
+        return None;
+    }
+    if identifier.starts_with(|ch: char| ch.is_ascii_digit()) {
+        identifier.insert(0, '_');
+    }
+    Some(identifier)
+}
+
+/// A field name for `name`, e.g. `content_type` for `content-type`.
+fn field_identifier(name: &str) -> String {
+    let mut identifier = name
+        .to_case(Case::Snake)
+        .chars()
+        .map(|ch| {
+            if ch.is_alphanumeric() || ch == '_' {
+                ch
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    if identifier.is_empty() || identifier.starts_with(|ch: char| ch.is_ascii_digit()) {
+        identifier.insert(0, '_');
+    }
+    match identifier.as_str() {
+        "self" | "super" | "crate" | "_" => format!("{}_", identifier),
+        ident if KEYWORDS.contains(&ident) => format!("r#{}", ident),
+        _ => identifier,
+    }
+}
+
+/// A field name without its `r#` prefix, as serde sees it.
+fn raw_name(name: &str) -> &str {
+    name.strip_prefix("r#").unwrap_or(name)
+}
+
+fn is_optional(occur: &Option<Occurrence>) -> bool {
+    matches!(
+        occur,
+        Some(Occurrence {
+            occur: Occur::Optional { .. }
+                | Occur::ZeroOrMore { .. }
+                | Occur::Exact {
+                    lower: None | Some(0),
+                    ..
+                },
+            ..
+        })
+    )
+}
+
+fn is_optional_once(occur: &Option<Occurrence>) -> bool {
+    matches!(
+        occur,
+        Some(Occurrence {
+            occur: Occur::Optional { .. },
+            ..
+        })
+    )
+}
+
+/// Whether a map is made of a single catch-all member (e.g.
+/// `{ * tstr => uint }`), which makes it a `BTreeMap`.
+fn is_record(group: &Group) -> bool {
+    matches!(
+        group.group_choices.as_slice(),
+        [GroupChoice { group_entries, .. }] if matches!(
+            group_entries.as_slice(),
+            [(GroupEntry::ValueMemberKey { ge, .. }, _)]
+                if matches!(ge.member_key, Some(MemberKey::Type1 { .. }))
+        )
+    )
+}
+
+/// The text values of a choice made of them only.
+fn text_values(t: &Type) -> Option<Vec<String>> {
+    if t.type_choices.len() < 2 {
+        return None;
+    }
+    t.type_choices
+        .iter()
+        .map(
+            |choice| match (&choice.type1.type2, &choice.type1.operator) {
+                (Type2::TextValue { value, .. }, None) => Some(unescape_text(value)),
+                _ => None,
+            },
+        )
+        .collect()
+}
+
+/// The other choice of a choice of two with `null` (e.g. `text / null`).
+fn nullable<'t, 'a>(t: &'t Type<'a>) -> Option<&'t Type1<'a>> {
+    let is_null = |t1: &Type1| {
+        t1.operator.is_none()
+            && matches!(
+                &t1.type2,
+                Type2::Typename { ident, .. } if matches!(ident.ident, "null" | "nil")
+            )
+    };
+    match t.type_choices.as_slice() {
+        [first, second] if is_null(&second.type1) => Some(&first.type1),
+        [first, second] if is_null(&first.type1) => Some(&second.type1),
+        _ => None,
+    }
+}
+
+/// The type of a range, from its bounds.
+fn range_type(lower: &Type2, upper: &Type2) -> &'static str {
+    match (lower, upper) {
+        (Type2::FloatValue { .. }, _) | (_, Type2::FloatValue { .. }) => "f64",
+        (Type2::IntValue { .. }, _) | (_, Type2::IntValue { .. }) => "i64",
+        _ => "u64",
+    }
+}
+
+/// The rule named by a member without a key (e.g. `attributes` in
+/// `{ attributes }`).
+fn group_reference<'a>(ge: &ValueMemberKeyEntry<'a>) -> Option<&'a str> {
+    if ge.member_key.is_some() {
+        return None;
+    }
+    match ge.entry_type.type_choices.as_slice() {
+        [choice] => match (&choice.type1.type2, &choice.type1.operator) {
+            (
+                Type2::Typename {
+                    ident,
+                    generic_args: None,
+                    ..
+                },
+                None,
+            ) => Some(ident.ident),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn type_references<'a>(t: &Type<'a>, references: &mut BTreeSet<&'a str>) {
+    for choice in &t.type_choices {
+        type2_references(&choice.type1.type2, references);
+        if let Some(op) = &choice.type1.operator {
+            type2_references(&op.type2, references);
+        }
+    }
+}
+
+fn type2_references<'a>(t2: &Type2<'a>, references: &mut BTreeSet<&'a str>) {
+    match t2 {
+        Type2::Typename { ident, .. }
+        | Type2::Unwrap { ident, .. }
+        | Type2::ChoiceFromGroup { ident, .. } => {
+            references.insert(ident.ident);
+        }
+        Type2::ParenthesizedType { pt, .. } => type_references(pt, references),
+        Type2::TaggedData { t, .. } => type_references(t, references),
+        Type2::Map { group, .. }
+        | Type2::Array { group, .. }
+        | Type2::ChoiceFromInlineGroup { group, .. } => group_references(group, references),
+        _ => {}
+    }
+}
+
+fn group_references<'a>(group: &Group<'a>, references: &mut BTreeSet<&'a str>) {
+    for choice in &group.group_choices {
+        for (entry, _) in &choice.group_entries {
+            entry_references(entry, references);
+        }
+    }
+}
+
+fn entry_references<'a>(entry: &GroupEntry<'a>, references: &mut BTreeSet<&'a str>) {
+    match entry {
+        GroupEntry::ValueMemberKey { ge, .. } => {
+            if let Some(MemberKey::Type1 { t1, .. }) = &ge.member_key {
+                type2_references(&t1.type2, references);
+            }
+            type_references(&ge.entry_type, references);
+        }
+        GroupEntry::TypeGroupname { ge, .. } => {
+            references.insert(ge.name.ident);
+        }
+        GroupEntry::InlineGroup { group, .. } => group_references(group, references),
+    }
+}
@@ -14,14 +14,62 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::io::Write;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    io::Write,
+};
 
-use cddl::{ast::Occurrence, visitor::Visitor, Error};
+use cddl::{
+    ast::{Occurrence, CDDL},
+    token::SocketPlug,
+    visitor::Visitor,
+    Error,
+};
 
-use crate::util::{is_alphaspace, split_namespaced, to_namespaced, to_pascalcase};
+use crate::{
+    amendments::merge_amendments,
+    docs::Docs,
+    generator::{diagnostics, with_instances, Diagnostic, Generator},
+    naming::{identifier, Names},
+    options::{Enums, Extensibility, Options, Profile, Renames},
+    printer::Printer,
+    unwrap::{choice_enums, unwrap},
+    util::{
+        byte_value, catch_all_group_type, catch_all_type, choice_groups, is_enum_value,
+        is_primitive_alias, is_safe_range, major_type_prelude, map_literals, member_key_name,
+        plain_groups, sockets, text_literal, to_base64url, to_string_literal, unescape_text,
+        well_known_tag, LiteralMembers, WellKnownTag,
+    },
+    vectors::array_groups,
+};
+
+mod guards;
+pub(crate) mod regexp;
 
 const MAX_ELEMENTS: usize = 1 << 3;
 
+/// Global types referenced by the output, which rules must not shadow.
+const RESERVED: &[&str] = &[
+    "any",
+    "bigint",
+    "boolean",
+    "never",
+    "number",
+    "object",
+    "string",
+    "symbol",
+    "undefined",
+    "unknown",
+    "Array",
+    "Flatten",
+    "Number",
+    "Object",
+    "Record",
+    "RegExp",
+    "Uint8Array",
+    "URL",
+];
+
 macro_rules! visit_array_element_impl {
     ($self:expr, $occur:expr, $spreaded_expr:expr, $spreadable_expr: expr) => {
         let (lower, upper) = calculate_occurrence($occur);
@@ -73,6 +121,8 @@ macro_rules! visit_array_element_impl {
 struct GroupChoiceContext {
     in_object: bool,
     is_first: bool,
+    /// Type of the members other than the named ones, if any.
+    catch_all: Option<String>,
 }
 
 struct Type1Context {
@@ -80,10 +130,34 @@ struct Type1Context {
 }
 
 struct PostambleOptions {
-    #[cfg(feature = "vector_groups")]
     print_flatten: bool,
 }
 
+/// The [`Generator`] of TypeScript types.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct TypeScript;
+
+impl Generator for TypeScript {
+    fn generate(
+        &self,
+        cddl: &CDDL,
+        source: &str,
+        options: &Options,
+        out: &mut dyn Write,
+    ) -> anyhow::Result<Vec<Diagnostic>> {
+        with_instances(cddl, options, |cddl| {
+            let mut engine = Engine::with_writers(out, Vec::new())
+                .with_options(options.clone())
+                .with_source(source);
+            engine.visit_cddl(cddl)?;
+            engine.print_postamble();
+            let (out, stderr) = engine.into_writers();
+            out.flush()?;
+            Ok(diagnostics(&stderr))
+        })
+    }
+}
+
 pub struct Engine<Stdout, Stderr>
 where
     Stdout: Write,
@@ -92,9 +166,37 @@ where
     in_comment: bool,
     nested_group_choices: Vec<GroupChoiceContext>,
     nested_type1: Vec<Type1Context>,
-    #[allow(dead_code)]
     postamble_options: PostambleOptions,
-    stdout: Stdout,
+    options: Options,
+    /// Referenced or defined sockets, keyed by name.
+    sockets: BTreeMap<String, SocketPlug>,
+    defined_sockets: BTreeSet<String>,
+    /// Value types of the group rules made of a single catch-all member,
+    /// keyed by rule name.
+    catch_all_groups: BTreeMap<String, String>,
+    /// Members of the rules that are a choice from a group of literal values,
+    /// keyed by rule name.
+    enums: BTreeMap<String, LiteralMembers>,
+    /// Literal members of the plain groups and of the rules that are plain
+    /// maps, keyed by rule name, which tell the variants of discriminated
+    /// unions apart in type guards.
+    plain_groups: BTreeMap<String, LiteralMembers>,
+    /// Literal members that every choice of a group rule has, keyed by rule
+    /// name, which tell apart the variants the group is used in.
+    choice_groups: BTreeMap<String, LiteralMembers>,
+    map_literals: BTreeMap<String, LiteralMembers>,
+    /// Group rules used as elements of arrays, which have a `Vector` tuple if
+    /// `vector_groups` is set.
+    array_groups: BTreeSet<String>,
+    /// Comments of the source, which document the rules and members.
+    docs: Docs,
+    /// CDDL name of the rule being emitted.
+    rule: String,
+    names: Names,
+    /// Names of the variants guarded so far, which no other variant may have,
+    /// with their values.
+    variant_names: HashMap<String, String>,
+    stdout: Printer<Stdout>,
     stderr: Stderr,
 }
 
@@ -119,42 +221,137 @@ impl<'a, 'b: 'a, 'c, Stdout: Write, Stderr: Write> Engine<Stdout, Stderr> {
             nested_group_choices: Vec::new(),
             nested_type1: Vec::new(),
             postamble_options: PostambleOptions {
-                #[cfg(feature = "vector_groups")]
                 print_flatten: false,
             },
-            stdout,
+            options: Options::default(),
+            sockets: BTreeMap::new(),
+            defined_sockets: BTreeSet::new(),
+            catch_all_groups: BTreeMap::new(),
+            enums: BTreeMap::new(),
+            plain_groups: BTreeMap::new(),
+            choice_groups: BTreeMap::new(),
+            map_literals: BTreeMap::new(),
+            array_groups: BTreeSet::new(),
+            docs: Docs::default(),
+            rule: String::new(),
+            names: Names::default(),
+            variant_names: HashMap::new(),
+            stdout: Printer::new(stdout),
             stderr,
         }
     }
+    pub fn with_options(mut self, options: Options) -> Engine<Stdout, Stderr> {
+        self.stdout.set_layout(options.layout.clone());
+        self.options = options;
+        self
+    }
+    /// Documents the output with the comments of `source`, the text the AST
+    /// given to `visit_cddl` was parsed from.
+    pub fn with_source(mut self, source: &str) -> Engine<Stdout, Stderr> {
+        self.docs = Docs::new(source);
+        self
+    }
     pub fn into_writers(self) -> (Stdout, Stderr) {
-        (self.stdout, self.stderr)
+        (self.stdout.into_inner(), self.stderr)
     }
     pub fn print_postamble(&mut self) {
-        #[cfg(feature = "vector_groups")]
         if self.postamble_options.print_flatten {
-            write!(
+            writeln!(
                 self.stdout,
                 "export type Flatten<T extends unknown[]> = T extends (infer S)[][] \
                     ? S[] \
                     : never;"
-            )
+            );
         }
     }
-    /// Requires all type choices to be strings.
-    fn visit_enum_type(&mut self, t: &'b cddl::ast::Type<'a>) -> cddl::visitor::Result<Error> {
-        for choice in &t.type_choices {
-            if let cddl::ast::Type2::TextValue { value, .. } = &choice.type1.type2 {
+    /// The members of a rule emitted as an enum, i.e. a choice from the
+    /// values of a group or of text values, named after their keys or
+    /// values respectively.
+    fn enum_members(&self, tr: &cddl::ast::TypeRule<'a>) -> Option<LiteralMembers> {
+        if let Some(members) = self.enums.get(tr.name.ident) {
+            return Some(members.clone());
+        }
+        if tr.name.socket.is_some() {
+            return None;
+        }
+        tr.value
+            .type_choices
+            .iter()
+            .map(|choice| match &choice.type1.type2 {
+                cddl::ast::Type2::TextValue { value, .. } if is_enum_value(value) => {
+                    Some((unescape_text(value), text_literal(value)))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+    /// Writes an enum in the selected representation, other than a union.
+    fn print_enum(&mut self, type_name: &str, members: LiteralMembers) {
+        let (names, collisions) = self
+            .names
+            .members(&self.rule, members.iter().map(|(name, _)| name.as_str()));
+        for collision in collisions {
+            writeln!(self.stderr, "{}", collision);
+        }
+        self.exit_comment();
+        match self.options.enums {
+            Enums::ConstEnum | Enums::Enum => {
+                let keyword = match self.options.enums {
+                    Enums::ConstEnum => "const enum",
+                    _ => "enum",
+                };
+                write!(self.stdout, "export {} {} {{", keyword, type_name);
+                for (name, (_, value)) in names.into_iter().zip(members) {
+                    writeln!(self.stdout, "{} = {},", name, value);
+                }
+                writeln!(self.stdout, "}}");
+            }
+            Enums::Object => {
+                write!(self.stdout, "export const {} = {{", type_name);
+                for (name, (_, value)) in names.into_iter().zip(members) {
+                    writeln!(self.stdout, "{}: {},", name, value);
+                }
+                writeln!(self.stdout, "}} as const;");
                 writeln!(
                     self.stdout,
-                    "{} = \"{}\",",
-                    to_pascalcase(value),
-                    value.to_string()
+                    "export type {} = (typeof {})[keyof typeof {}];",
+                    type_name, type_name, type_name
                 );
-            } else {
-                panic!("Called `visit_enum_type` on non-textual type");
             }
+            Enums::Union => unreachable!("unions are emitted as types"),
+        }
+    }
+    /// Writes a template literal type for string types constrained by a
+    /// regular expression, if enabled and the pattern is simple enough.
+    fn visit_maybe_template_literal(&mut self, t1: &'b cddl::ast::Type1<'a>) -> bool {
+        if !self.options.template_literals {
+            return false;
+        }
+        let Some(cddl::ast::Operator {
+            operator: cddl::ast::RangeCtlOp::CtlOp { ctrl, .. },
+            type2: cddl::ast::Type2::TextValue { value, .. },
+            ..
+        }) = &t1.operator
+        else {
+            return false;
+        };
+        if !matches!(
+            &t1.type2,
+            cddl::ast::Type2::Typename { ident, .. } if matches!(ident.ident, "tstr" | "text")
+        ) {
+            return false;
+        }
+        let template = match ctrl {
+            cddl::token::ControlOperator::REGEXP => regexp::to_template_literal(value, true),
+            cddl::token::ControlOperator::PCRE => regexp::to_template_literal(value, false),
+            _ => None,
+        };
+        if let Some(template) = template {
+            write!(self.stdout, "{}", template);
+            true
+        } else {
+            false
         }
-        Ok(())
     }
     fn visit_type_for_comment_inner(
         &mut self,
@@ -181,6 +378,21 @@ impl<'a, 'b: 'a, 'c, Stdout: Write, Stderr: Write> Engine<Stdout, Stderr> {
             writeln!(self.stdout, " */");
         }
     }
+    /// Writes the lines of a documentation comment, ahead of the notes about
+    /// the constraints of the type.
+    fn visit_doc(&mut self, doc: Option<String>) {
+        let Some(doc) = doc else {
+            return;
+        };
+        self.enter_comment();
+        for line in doc.lines() {
+            if line.is_empty() {
+                writeln!(self.stdout, " *");
+            } else {
+                writeln!(self.stdout, " * {}", line.replace("*/", "*\\/"));
+            }
+        }
+    }
     fn visit_type_for_comment(
         &mut self,
         t: &'b cddl::ast::Type<'a>,
@@ -292,6 +504,7 @@ impl<'a, 'b: 'a, 'c, Stdout: Write, Stderr: Write> Engine<Stdout, Stderr> {
         self.nested_group_choices.push(GroupChoiceContext {
             in_object: false,
             is_first: true,
+            catch_all: None,
         });
         if gc.group_entries.is_empty() {
             self.enter_array();
@@ -345,10 +558,97 @@ impl<'a, 'b: 'a, 'c, Stdout: Write, Stderr: Write> Engine<Stdout, Stderr> {
         if let Some(group) = self.nested_group_choices.last_mut() {
             if group.in_object {
                 group.in_object = false;
+                if let Some(t) = &group.catch_all {
+                    write!(self.stdout, ",[key: string]:{}", t);
+                }
                 write!(self.stdout, "}}");
             }
         }
     }
+    /// The type of an index signature, which includes the types of the named
+    /// members, as TypeScript requires them to conform to it.
+    fn index_signature_type(
+        &mut self,
+        mut types: Vec<String>,
+        entries: &[&'b cddl::ast::GroupEntry<'a>],
+    ) -> Result<String, Error> {
+        if !types
+            .iter()
+            .any(|t| matches!(t.as_str(), "(any)" | "(unknown)"))
+        {
+            for entry in entries {
+                let cddl::ast::GroupEntry::ValueMemberKey { ge, .. } = entry else {
+                    continue;
+                };
+                if is_object_member(entry) {
+                    let t = self.render_type(&ge.entry_type)?;
+                    if !types.contains(&t) {
+                        types.push(t);
+                    }
+                }
+            }
+        }
+        Ok(types.join("|"))
+    }
+    fn index_catch_all_groups(
+        &mut self,
+        cddl: &'b cddl::ast::CDDL<'a>,
+    ) -> cddl::visitor::Result<Error> {
+        for rule in &cddl.rules {
+            let cddl::ast::Rule::Group { rule, .. } = rule else {
+                continue;
+            };
+            if let Some(t) = catch_all_group_type(rule) {
+                let t = self.render_type(t)?;
+                self.catch_all_groups.insert(rule.name.ident.to_string(), t);
+            }
+        }
+        Ok(())
+    }
+    /// Literal members a group adds to the maps it is used in. Catch-all
+    /// groups add none, but keep the maps objects, and choices of groups add
+    /// those of every choice.
+    fn map_group_literals(&self, name: &str) -> Option<LiteralMembers> {
+        self.plain_groups
+            .get(name)
+            .or_else(|| self.choice_groups.get(name))
+            .cloned()
+            .or_else(|| {
+                self.catch_all_groups
+                    .contains_key(name)
+                    .then(LiteralMembers::new)
+            })
+    }
+    /// Writes a type to a string rather than the output.
+    fn render_type(&mut self, t: &'b cddl::ast::Type<'a>) -> Result<String, Error> {
+        let mut engine =
+            Engine::with_writers(Vec::new(), Vec::new()).with_options(self.options.clone());
+        engine.catch_all_groups = self.catch_all_groups.clone();
+        engine.rule = self.rule.clone();
+        engine.names = self.names.clone();
+        engine.array_groups = self.array_groups.clone();
+        engine.visit_type(t)?;
+        self.sockets.append(&mut engine.sockets);
+        self.postamble_options.print_flatten |= engine.postamble_options.print_flatten;
+        Ok(String::from_utf8(engine.stdout.take_unformatted()).unwrap())
+    }
+    /// The value type if the entry is a catch-all member or a catch-all group.
+    fn visit_catch_all(
+        &mut self,
+        entry: &'b cddl::ast::GroupEntry<'a>,
+    ) -> Result<Option<String>, Error> {
+        if let Some(t) = catch_all_type(entry) {
+            return self.render_type(t).map(Some);
+        }
+        match entry {
+            cddl::ast::GroupEntry::TypeGroupname { ge, .. }
+                if ge.occur.is_none() && ge.generic_args.is_none() && ge.name.socket.is_none() =>
+            {
+                Ok(self.catch_all_groups.get(ge.name.ident).cloned())
+            }
+            _ => Ok(None),
+        }
+    }
     fn visit_array_entry(
         &mut self,
         entry: &'b cddl::ast::GroupEntry<'a>,
@@ -373,6 +673,7 @@ impl<'a, 'b: 'a, 'c, Stdout: Write, Stderr: Write> Engine<Stdout, Stderr> {
         &mut self,
         entry: &'b cddl::ast::ValueMemberKeyEntry<'a>,
     ) -> cddl::visitor::Result<Error> {
+        self.visit_doc(self.docs.member(entry));
         self.visit_type_for_comment(&entry.entry_type)?;
         visit_array_element_impl!(
             self,
@@ -394,7 +695,7 @@ impl<'a, 'b: 'a, 'c, Stdout: Write, Stderr: Write> Engine<Stdout, Stderr> {
             return;
         };
         if let cddl::ast::MemberKey::Bareword { ident, .. } = mk {
-            write!(self.stdout, "{}:", &ident);
+            write!(self.stdout, "{}:", identifier(ident.ident, RESERVED));
         } else {
             writeln!(
                 self.stderr,
@@ -412,39 +713,44 @@ impl<'a, 'b: 'a, 'c, Stdout: Write, Stderr: Write> Engine<Stdout, Stderr> {
             self,
             &entry.occur,
             {
-                #[cfg(not(feature = "vector_groups"))]
-                {
-                    write!(self.stdout, "...");
-                    self.visit_identifier_with_args(&entry.name, &entry.generic_args)?;
-                }
-                #[cfg(feature = "vector_groups")]
-                {
-                    write!(self.stdout, "...");
-                    self.visit_identifier_with_args(&entry.name, &entry.generic_args)?;
-                    write!(self.stdout, "Vector");
-                }
+                write!(self.stdout, "...");
+                self.visit_array_group(entry)?;
             },
             {
-                #[cfg(not(feature = "vector_groups"))]
-                {
-                    self.visit_identifier_with_args(&entry.name, &entry.generic_args)?;
-                    write!(self.stdout, "[number]");
-                    write!(self.stdout, "[]");
-                }
-                #[cfg(feature = "vector_groups")]
-                {
+                if self.is_vector(&entry.name) {
                     self.postamble_options.print_flatten = true;
                     write!(self.stdout, "Flatten<");
-                    self.visit_identifier_with_args(&entry.name, &entry.generic_args)?;
-                    write!(self.stdout, "Vector");
+                    self.visit_array_group(entry)?;
                     write!(self.stdout, "[]");
                     write!(self.stdout, ">");
+                } else {
+                    self.visit_array_group(entry)?;
+                    write!(self.stdout, "[number]");
+                    write!(self.stdout, "[]");
                 }
             }
         );
         Ok(())
     }
 
+    /// Whether a group used in an array is emitted as its `Vector` tuple.
+    fn is_vector(&self, name: &cddl::ast::Identifier<'a>) -> bool {
+        self.options.vector_groups && self.array_groups.contains(name.ident)
+    }
+
+    /// Writes a group used as elements of an array: its `Vector` tuple if it
+    /// has one, or the group itself.
+    fn visit_array_group(
+        &mut self,
+        entry: &cddl::ast::TypeGroupnameEntry<'a>,
+    ) -> cddl::visitor::Result<Error> {
+        self.visit_identifier(&entry.name)?;
+        if self.is_vector(&entry.name) {
+            write!(self.stdout, "Vector");
+        }
+        self.print_generic_args(&entry.generic_args)
+    }
+
     fn visit_inline_array_entry(
         &mut self,
         occur: &Option<cddl::ast::Occurrence>,
@@ -454,33 +760,13 @@ impl<'a, 'b: 'a, 'c, Stdout: Write, Stderr: Write> Engine<Stdout, Stderr> {
             self,
             &occur,
             {
-                #[cfg(not(feature = "vector_groups"))]
-                {
-                    write!(self.stdout, "...");
-                    self.visit_array(&group)?;
-                }
-                #[cfg(feature = "vector_groups")]
-                {
-                    write!(self.stdout, "...");
-                    self.visit_array(&group)?;
-                    write!(self.stdout, "Vector");
-                }
+                write!(self.stdout, "...");
+                self.visit_array(group)?;
             },
             {
-                #[cfg(not(feature = "vector_groups"))]
-                {
-                    self.visit_array(&group)?;
-                    write!(self.stdout, "[number]");
-                    write!(self.stdout, "[]");
-                }
-                #[cfg(feature = "vector_groups")]
-                {
-                    self.postamble_options.print_flatten = true;
-                    write!(self.stdout, "Flatten<");
-                    self.visit_array(&group)?;
-                    write!(self.stdout, "[]");
-                    write!(self.stdout, ">");
-                }
+                self.visit_array(group)?;
+                write!(self.stdout, "[number]");
+                write!(self.stdout, "[]");
             }
         );
         Ok(())
@@ -491,7 +777,7 @@ impl<'a, 'b: 'a, 'c, Stdout: Write, Stderr: Write> Engine<Stdout, Stderr> {
         ident: &cddl::ast::Identifier<'a>,
         params: &Option<cddl::ast::GenericParams<'a>>,
     ) -> cddl::visitor::Result<Error> {
-        self.visit_identifier(ident)?;
+        write!(self.stdout, "{}", ident.ident);
         if let Some(params) = params {
             write!(self.stdout, "<");
             for param in &params.params {
@@ -509,6 +795,13 @@ impl<'a, 'b: 'a, 'c, Stdout: Write, Stderr: Write> Engine<Stdout, Stderr> {
         params: &Option<cddl::ast::GenericArgs<'a>>,
     ) -> cddl::visitor::Result<Error> {
         self.visit_identifier(ident)?;
+        self.print_generic_args(params)
+    }
+
+    fn print_generic_args(
+        &mut self,
+        params: &Option<cddl::ast::GenericArgs<'a>>,
+    ) -> cddl::visitor::Result<Error> {
         if let Some(params) = params {
             write!(self.stdout, "<");
             for param in &params.args {
@@ -519,46 +812,230 @@ impl<'a, 'b: 'a, 'c, Stdout: Write, Stderr: Write> Engine<Stdout, Stderr> {
         }
         Ok(())
     }
+
+    fn define_socket(&mut self, name: &cddl::ast::Identifier<'a>) {
+        if let Some(socket) = name.socket {
+            self.sockets.insert(name.ident.to_string(), socket);
+            self.defined_sockets.insert(name.ident.to_string());
+        }
+    }
+
+    /// Sockets are emitted as interfaces so that they can be extended through
+    /// declaration merging. Type sockets are unions of the interface's
+    /// property types; group sockets are the interface itself.
+    fn print_sockets(&mut self) {
+        for (name, socket) in std::mem::take(&mut self.sockets) {
+            let (namespaces, type_name) = self.names.split_rule(&name);
+            for namespace in &namespaces {
+                writeln!(self.stdout, "export namespace {} {{", namespace);
+            }
+            match socket {
+                SocketPlug::TYPE => {
+                    writeln!(
+                        self.stdout,
+                        "/** Add properties to extend `${}`; their types are added to the choices. */",
+                        name
+                    );
+                    writeln!(self.stdout, "export interface {}Socket {{}}", type_name);
+                    if !self.defined_sockets.contains(&name) {
+                        writeln!(
+                            self.stdout,
+                            "export type {} = {}Socket[keyof {}Socket];",
+                            type_name, type_name, type_name
+                        );
+                    }
+                }
+                SocketPlug::GROUP => {
+                    writeln!(self.stdout, "/** Add properties to extend `$${}`. */", name);
+                    writeln!(self.stdout, "export interface {}Socket {{}}", type_name);
+                    if !self.defined_sockets.contains(&name) {
+                        writeln!(
+                            self.stdout,
+                            "export type {} = {}Socket;",
+                            type_name, type_name
+                        );
+                    }
+                }
+            }
+            for _ in &namespaces {
+                writeln!(self.stdout, "}}");
+            }
+        }
+    }
+
+    fn visit_prelude(&mut self, ident: &'static str) -> cddl::visitor::Result<Error> {
+        self.visit_identifier(&cddl::ast::Identifier {
+            ident,
+            socket: None,
+            span: Default::default(),
+        })
+    }
+
+    /// Well-known tags are mapped to their natural representation, or to their
+    /// declared content type if it differs from the tag's own. Other tags are
+    /// transparent unless `cbor_tags` is set.
+    fn visit_tagged_data(
+        &mut self,
+        tag: &Option<cddl::token::TagConstraint<'a>>,
+        t: Option<&'b cddl::ast::Type<'a>>,
+    ) -> cddl::visitor::Result<Error> {
+        let tag = tag.as_ref().and_then(|tag| tag.as_literal());
+        match well_known_tag(tag, t) {
+            Some(WellKnownTag::Prelude(prelude)) => return self.visit_prelude(prelude),
+            Some(WellKnownTag::Content(t)) => return self.visit_type(t),
+            None => {}
+        }
+        if self.options.cbor_tags {
+            match tag {
+                Some(tag) => write!(self.stdout, "{{tag: {}; value: ", tag),
+                None => write!(self.stdout, "{{tag: number; value: "),
+            };
+        }
+        match t {
+            Some(t) => self.visit_type(t)?,
+            None => {
+                write!(self.stdout, "unknown");
+            }
+        }
+        if self.options.cbor_tags {
+            write!(self.stdout, "}}");
+        }
+        Ok(())
+    }
+
+    fn visit_major_type(
+        &mut self,
+        mt: u8,
+        constraint: &Option<cddl::token::TagConstraint<'a>>,
+    ) -> cddl::visitor::Result<Error> {
+        let constraint_value = constraint
+            .as_ref()
+            .and_then(|constraint| constraint.as_literal());
+        if let Some(prelude) = major_type_prelude(mt, constraint_value) {
+            return self.visit_prelude(prelude);
+        }
+        match mt {
+            4 => {
+                write!(self.stdout, "unknown[]");
+            }
+            5 => {
+                write!(self.stdout, "Record<string, unknown>");
+            }
+            6 => self.visit_tagged_data(constraint, None)?,
+            7 => match constraint_value {
+                Some(_) => {
+                    write!(self.stdout, "unknown");
+                }
+                None => {
+                    write!(self.stdout, "boolean | null | undefined | number");
+                }
+            },
+            _ => {
+                writeln!(self.stderr, "Unknown major type: {}", mt);
+                write!(self.stdout, "unknown");
+            }
+        }
+        Ok(())
+    }
 }
 
 impl<'a, 'b: 'a, Stdout: Write, Stderr: Write> Visitor<'a, 'b, Error> for Engine<Stdout, Stderr> {
+    fn visit_cddl(&mut self, cddl: &'b cddl::ast::CDDL<'a>) -> cddl::visitor::Result<Error> {
+        let cddl = &merge_amendments(cddl);
+        self.enums = choice_enums(cddl);
+        let (cddl, unwraps) = unwrap(cddl);
+        let cddl = &cddl;
+        for unwrap in unwraps {
+            writeln!(self.stderr, "{}", unwrap);
+        }
+        if self.options.vector_groups {
+            self.array_groups = array_groups(cddl);
+        }
+        let sockets = sockets(cddl);
+        let (names, collisions) = Names::new(cddl, &self.options.renames, RESERVED, |ident| {
+            let mut affixes = Vec::new();
+            if self.array_groups.contains(ident) {
+                affixes.push(("", "Vector"));
+            }
+            if sockets.contains(ident) {
+                affixes.push(("", "Socket"));
+            }
+            if self.options.type_guards {
+                affixes.push(("is", ""));
+            }
+            affixes
+        });
+        self.names = names;
+        for collision in collisions {
+            writeln!(self.stderr, "{}", collision);
+        }
+        self.index_catch_all_groups(cddl)?;
+        if self.options.type_guards {
+            // Types keep the keys members are serialized under.
+            let renames = Renames::default();
+            self.plain_groups = plain_groups(cddl, &renames);
+            self.choice_groups =
+                choice_groups(cddl, &renames, &|name| self.map_group_literals(name));
+            self.map_literals = map_literals(cddl, &renames, &|name| self.map_group_literals(name));
+        }
+        cddl::visitor::walk_cddl(self, cddl)?;
+        self.print_sockets();
+        Ok(())
+    }
     fn visit_identifier(
         &mut self,
         ident: &cddl::ast::Identifier<'a>,
     ) -> cddl::visitor::Result<Error> {
+        if let Some(socket) = ident.socket {
+            self.sockets.insert(ident.ident.to_string(), socket);
+            write!(self.stdout, "{}", self.names.rule(ident.ident));
+            return Ok(());
+        }
         match ident.ident {
             "bool" => write!(self.stdout, "boolean"),
-            "uint" | "nint" | "int" | "float16" | "float32" | "float64" | "float16-32"
-            | "float32-64" | "float" | "number" => write!(self.stdout, "number"),
-            "biguint" | "bignint" | "bigint" => write!(self.stdout, "bigint"),
-            "bstr" | "bytes" => write!(self.stdout, "Uint8Array"),
+            "uint" | "nint" | "int" => match self.options.profile {
+                Profile::Json => write!(self.stdout, "number"),
+                Profile::Cbor => write!(self.stdout, "(number | bigint)"),
+            },
+            "float16" | "float32" | "float64" | "float16-32" | "float32-64" | "float"
+            | "number" => write!(self.stdout, "number"),
+            "biguint" | "bignint" | "bigint" => match self.options.profile {
+                Profile::Json => write!(self.stdout, "number"),
+                Profile::Cbor => write!(self.stdout, "bigint"),
+            },
+            "bstr" | "bytes" | "encoded-cbor" => match self.options.profile {
+                Profile::Json => write!(self.stdout, "string"),
+                Profile::Cbor => write!(self.stdout, "Uint8Array"),
+            },
             "tstr" | "text" => write!(self.stdout, "string"),
             "any" => write!(self.stdout, "any"),
             "nil" | "null" => write!(self.stdout, "null"),
             "true" => write!(self.stdout, "true"),
-            "uri" => write!(self.stdout, "URL"),
+            "uri" => write!(self.stdout, "string"),
             "regexp" => write!(self.stdout, "RegExp"),
+            "tdate" | "b64url" | "b64legacy" | "mime-message" => write!(self.stdout, "string"),
+            "time" => write!(self.stdout, "number"),
+            "cbor-any" => write!(self.stdout, "any"),
             "false" => write!(self.stdout, "false"),
             "undefined" => write!(self.stdout, "undefined"),
-            ident => write!(self.stdout, "{}", to_namespaced(ident)),
+            ident => write!(self.stdout, "{}", self.names.rule(ident)),
         };
         Ok(())
     }
     fn visit_type_rule(&mut self, tr: &'b cddl::ast::TypeRule<'a>) -> cddl::visitor::Result<Error> {
-        let (namespaces, type_name) = split_namespaced(&tr.name);
+        self.define_socket(&tr.name);
+        self.docs.enter_rule(&tr.name);
+        self.rule = tr.name.ident.to_string();
+        let (namespaces, type_name) = self.names.split_rule(tr.name.ident);
         for namespace in &namespaces {
             writeln!(self.stdout, "export namespace {} {{", namespace);
         }
-        if tr.value.type_choices.iter().all(|choice| {
-            if let cddl::ast::Type2::TextValue { value, .. } = &choice.type1.type2 {
-                is_alphaspace(value)
-            } else {
-                false
-            }
-        }) {
-            write!(self.stdout, "export const enum {} {{", type_name);
-            self.visit_enum_type(&tr.value)?;
-            writeln!(self.stdout, "}}");
+        self.visit_doc(self.docs.type_rule(tr));
+        let members = self
+            .enum_members(tr)
+            .filter(|_| self.options.enums != Enums::Union);
+        if let Some(members) = members {
+            self.print_enum(&type_name, members);
         } else {
             self.visit_type_for_comment(&tr.value)?;
             write!(self.stdout, "export type ");
@@ -572,8 +1049,38 @@ impl<'a, 'b: 'a, Stdout: Write, Stderr: Write> Visitor<'a, 'b, Error> for Engine
             )?;
             write!(self.stdout, " = ");
             self.visit_type(&tr.value)?;
+            if tr.name.socket.is_some() {
+                write!(
+                    self.stdout,
+                    " | {}Socket[keyof {}Socket]",
+                    type_name, type_name
+                );
+            }
+            if is_primitive_alias(tr) && self.options.brands.contains(tr.name.ident) {
+                write!(
+                    self.stdout,
+                    " & {{ readonly __brand: {} }}",
+                    to_string_literal(self.names.rule(tr.name.ident))
+                );
+            }
             writeln!(self.stdout, ";");
         }
+        if self.options.type_guards && tr.name.socket.is_none() {
+            let mut guards = guards::Guards::new(&self.options, &self.names);
+            let guard = guards.type_rule(tr);
+            let mut variant_names = std::mem::take(&mut self.variant_names);
+            let (variant_guards, collisions) = guards.variant_guards(
+                tr,
+                &self.map_literals,
+                &|name| self.map_group_literals(name),
+                &mut variant_names,
+            );
+            self.variant_names = variant_names;
+            write!(self.stdout, "{}{}", guard, variant_guards);
+            for diagnostic in guards.diagnostics.iter().chain(&collisions) {
+                writeln!(self.stderr, "{}", diagnostic);
+            }
+        }
         for _ in &namespaces {
             writeln!(self.stdout, "}}");
         }
@@ -594,7 +1101,10 @@ impl<'a, 'b: 'a, Stdout: Write, Stderr: Write> Visitor<'a, 'b, Error> for Engine
         &mut self,
         gr: &'b cddl::ast::GroupRule<'a>,
     ) -> cddl::visitor::Result<Error> {
-        let (namespaces, type_name) = split_namespaced(&gr.name);
+        self.define_socket(&gr.name);
+        self.docs.enter_rule(&gr.name);
+        self.rule = gr.name.ident.to_string();
+        let (namespaces, type_name) = self.names.split_rule(gr.name.ident);
         for namespace in &namespaces {
             writeln!(self.stdout, "export namespace {} {{", namespace);
         }
@@ -618,6 +1128,8 @@ impl<'a, 'b: 'a, Stdout: Write, Stderr: Write> Visitor<'a, 'b, Error> for Engine
         //
         // This requires us to build to types in case of usage: one for use as a
         // map and the other for use as an array.
+        self.visit_doc(self.docs.group_rule(gr));
+        self.exit_comment();
         write!(self.stdout, "export type ");
         self.visit_identifier_with_params(
             &cddl::ast::Identifier {
@@ -629,20 +1141,29 @@ impl<'a, 'b: 'a, Stdout: Write, Stderr: Write> Visitor<'a, 'b, Error> for Engine
         )?;
         write!(self.stdout, " = ");
         self.visit_group_choice(&choice)?;
+        if gr.name.socket.is_some() {
+            write!(self.stdout, " & {}Socket", type_name);
+        }
         writeln!(self.stdout, ";");
+        if self.options.type_guards && gr.name.socket.is_none() {
+            let mut guards = guards::Guards::new(&self.options, &self.names);
+            let guard = guards.group_rule(gr);
+            write!(self.stdout, "{}", guard);
+            for diagnostic in &guards.diagnostics {
+                writeln!(self.stderr, "{}", diagnostic);
+            }
+        }
 
-        #[cfg(feature = "vector_groups")]
-        {
-            writeln!(self.stdout, "export type ");
+        if self.is_vector(&gr.name) {
+            write!(self.stdout, "export type ");
             self.visit_identifier_with_params(
                 &cddl::ast::Identifier {
-                    ident: &type_name,
+                    ident: &format!("{}Vector", type_name),
                     socket: None,
                     span: Default::default(),
                 },
                 &gr.generic_params,
             )?;
-            write!(self.stdout, "Vector");
             write!(self.stdout, " = ");
             self.visit_array_choice(&choice)?;
             writeln!(self.stdout, ";");
@@ -692,18 +1213,21 @@ impl<'a, 'b: 'a, Stdout: Write, Stderr: Write> Visitor<'a, 'b, Error> for Engine
     ) -> cddl::visitor::Result<Error> {
         self.print_group_joiner();
         self.enter_map();
+        self.visit_doc(self.docs.member(entry));
         self.visit_type_for_comment(&entry.entry_type)?;
 
-        let mk = entry.member_key.as_ref().expect(&format!(
-            "Expected member key for type {} since the current ambient rule is a map. \
-            Did you mean to declare {} with parenthesis (`( .. )`) \
-            instead of brackets (`{{ .. }}`)?",
-            entry.entry_type, entry.entry_type
-        ));
-        self.visit_memberkey(&mk)?;
+        let Some(mk) = entry.member_key.as_ref() else {
+            return Err(Error::CDDL(format!(
+                "Expected member key for type {} since the current ambient rule is a map. \
+                Did you mean to declare {} with parenthesis (`( .. )`) \
+                instead of brackets (`{{ .. }}`)?",
+                entry.entry_type, entry.entry_type
+            )));
+        };
+        self.visit_memberkey(mk)?;
 
         if matches!(calculate_occurrence(&entry.occur), (0, max) if max > 0)
-            && !matches!(&mk, cddl::ast::MemberKey::Type1 { is_cut: false, .. })
+            && !matches!(mk, cddl::ast::MemberKey::Type1 { is_cut: false, .. })
         {
             write!(self.stdout, "?");
         }
@@ -738,11 +1262,33 @@ impl<'a, 'b: 'a, Stdout: Write, Stderr: Write> Visitor<'a, 'b, Error> for Engine
                 Ok(())
             }
             false => {
+                // Catch-all members alongside named members become an index
+                // signature of the object rather than an intersection.
+                let mut entries = Vec::new();
+                let mut catch_alls = Vec::new();
+                for (entry, _) in &gc.group_entries {
+                    match self.visit_catch_all(entry)? {
+                        Some(t) => catch_alls.push(t),
+                        None => entries.push(entry),
+                    }
+                }
+                if !entries.iter().any(|entry| is_object_member(entry)) {
+                    entries = gc.group_entries.iter().map(|(entry, _)| entry).collect();
+                    catch_alls.clear();
+                }
+                let mut seen = BTreeSet::new();
+                catch_alls.retain(|t| seen.insert(t.clone()));
+                let catch_all = match self.options.extensibility {
+                    Extensibility::Closed => None,
+                    _ if catch_alls.is_empty() => None,
+                    _ => Some(self.index_signature_type(catch_alls, &entries)?),
+                };
                 self.nested_group_choices.push(GroupChoiceContext {
                     in_object: false,
                     is_first: true,
+                    catch_all,
                 });
-                for (index, (entry, _)) in gc.group_entries.iter().enumerate() {
+                for (index, entry) in entries.into_iter().enumerate() {
                     self.nested_group_choices.last_mut().unwrap().is_first = index == 0;
                     self.visit_group_entry(entry)?;
                 }
@@ -767,17 +1313,9 @@ impl<'a, 'b: 'a, Stdout: Write, Stderr: Write> Visitor<'a, 'b, Error> for Engine
                 }
                 write!(self.stdout, "]");
             }
-            cddl::ast::MemberKey::Bareword { ident, .. } => {
-                write!(self.stdout, "\"{}\"", &ident);
-            }
-            cddl::ast::MemberKey::Value { value, .. } => {
-                match value {
-                    cddl::token::Value::INT(value) => write!(self.stdout, "\"{}\"", value),
-                    cddl::token::Value::UINT(value) => write!(self.stdout, "\"{}\"", value),
-                    cddl::token::Value::FLOAT(value) => write!(self.stdout, "\"{}\"", value),
-                    cddl::token::Value::TEXT(value) => write!(self.stdout, "\"{}\"", value),
-                    cddl::token::Value::BYTE(value) => write!(self.stdout, "\"{}\"", value),
-                };
+            cddl::ast::MemberKey::Bareword { .. } | cddl::ast::MemberKey::Value { .. } => {
+                let key = member_key_name(mk).unwrap();
+                write!(self.stdout, "{}", to_string_literal(key));
             }
             cddl::ast::MemberKey::NonMemberKey { .. } => {
                 unimplemented!()
@@ -795,6 +1333,15 @@ impl<'a, 'b: 'a, Stdout: Write, Stderr: Write> Visitor<'a, 'b, Error> for Engine
                 })
             ),
         });
+        if self.visit_maybe_template_literal(t1) {
+            self.nested_type1.pop();
+            return Ok(());
+        }
+        if self.options.profile == Profile::Cbor && !is_safe_range(t1) {
+            write!(self.stdout, "(number | bigint)");
+            self.nested_type1.pop();
+            return Ok(());
+        }
         self.visit_type2(&t1.type2)?;
         if let Some(cddl::ast::Operator {
             operator: cddl::ast::RangeCtlOp::CtlOp { ctrl, .. },
@@ -835,6 +1382,22 @@ impl<'a, 'b: 'a, Stdout: Write, Stderr: Write> Visitor<'a, 'b, Error> for Engine
             cddl::ast::Type2::Any { .. } => {
                 write!(self.stdout, "unknown");
             }
+            // Choices from groups are resolved beforehand when they can be.
+            cddl::ast::Type2::ChoiceFromInlineGroup { .. }
+            | cddl::ast::Type2::ChoiceFromGroup { .. } => {
+                writeln!(
+                    self.stderr,
+                    "In `{}`, `{}` could not be resolved; it is `unknown`.",
+                    self.rule, t2
+                );
+                write!(self.stdout, "unknown");
+            }
+            cddl::ast::Type2::TaggedData { tag, t, .. } => {
+                self.visit_tagged_data(tag, Some(t))?;
+            }
+            cddl::ast::Type2::DataMajorType { mt, constraint, .. } => {
+                self.visit_major_type(*mt, constraint)?;
+            }
             // The default has the correct behavior for the rest of the cases.
             t2 => {
                 cddl::visitor::walk_type2(self, t2)?;
@@ -844,7 +1407,13 @@ impl<'a, 'b: 'a, Stdout: Write, Stderr: Write> Visitor<'a, 'b, Error> for Engine
     }
 
     fn visit_value(&mut self, value: &cddl::token::Value<'a>) -> cddl::visitor::Result<Error> {
-        if self.nested_type1.last().unwrap().use_generic {
+        let use_generic = self.nested_type1.last().unwrap().use_generic;
+        if matches!(value, cddl::token::Value::BYTE(_))
+            && (use_generic || (!self.in_comment && self.options.profile == Profile::Cbor))
+        {
+            return self.visit_prelude("bytes");
+        }
+        if use_generic {
             match value {
                 cddl::token::Value::INT(_)
                 | cddl::token::Value::UINT(_)
@@ -858,10 +1427,37 @@ impl<'a, 'b: 'a, Stdout: Write, Stderr: Write> Visitor<'a, 'b, Error> for Engine
                 cddl::token::Value::INT(value) => write!(self.stdout, "{}", value),
                 cddl::token::Value::UINT(value) => write!(self.stdout, "{}", value),
                 cddl::token::Value::FLOAT(value) => write!(self.stdout, "{}", value),
-                cddl::token::Value::TEXT(value) => write!(self.stdout, "\"{}\"", value),
-                cddl::token::Value::BYTE(value) => write!(self.stdout, "\"{}\"", value),
+                cddl::token::Value::TEXT(value) => write!(self.stdout, "{}", text_literal(value)),
+                cddl::token::Value::BYTE(value) if self.in_comment => {
+                    write!(self.stdout, "{}", to_string_literal(value.to_string()))
+                }
+                // JSON has the base64url of the bytes.
+                cddl::token::Value::BYTE(value) => match byte_value(value) {
+                    Some(bytes) => {
+                        write!(self.stdout, "{}", to_string_literal(to_base64url(&bytes)))
+                    }
+                    None => {
+                        writeln!(
+                            self.stderr,
+                            "In `{}`, `{}` is not a valid byte string; it is `string`.",
+                            self.rule, value
+                        );
+                        write!(self.stdout, "string")
+                    }
+                },
             };
         }
         Ok(())
     }
 }
+
+/// Whether the entry is a named member of a map.
+fn is_object_member(entry: &cddl::ast::GroupEntry) -> bool {
+    matches!(
+        entry,
+        cddl::ast::GroupEntry::ValueMemberKey { ge, .. } if matches!(
+            ge.member_key,
+            Some(cddl::ast::MemberKey::Bareword { .. } | cddl::ast::MemberKey::Value { .. })
+        )
+    )
+}
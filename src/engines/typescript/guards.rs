@@ -0,0 +1,791 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Generation of runtime type guards (`isFoo(value): value is Foo`).
+//!
+//! Guards are built as JavaScript expressions over a value expression. Values
+//! that are not plain variables cannot be narrowed, so they are cast wherever
+//! their type matters. Constructs that cannot be checked (sockets, unwraps,
+//! group choices inside arrays, etc.) are accepted.
+
+use std::collections::{BTreeMap, HashMap};
+
+use cddl::{
+    ast::{Group, GroupChoice, GroupEntry, MemberKey, Type, Type1, Type2},
+    token::ByteValue,
+};
+
+use crate::{
+    naming::Names,
+    options::{Options, Profile},
+    util::{
+        byte_value, find_discriminator, literal_members, major_type_prelude, member_key_name,
+        text_literal, to_base64url, to_string_literal, well_known_tag, LiteralMembers,
+        WellKnownTag,
+    },
+};
+
+use super::{calculate_occurrence, regexp};
+
+pub(crate) struct Guards<'o> {
+    options: &'o Options,
+    names: &'o Names,
+    /// Parameters of the generic rule whose guard is being built.
+    generic_params: Vec<String>,
+    /// Nesting depth of callbacks, used to name their parameters.
+    depth: usize,
+    /// Bareword and value keys of the group choice being checked, which are
+    /// not constrained by its computed keys.
+    named_keys: Vec<String>,
+    /// CDDL name of the rule whose guard is being built.
+    rule: String,
+    /// Constraints the guards do not check.
+    pub(crate) diagnostics: Vec<String>,
+}
+
+fn literal(t2: &Type2) -> Option<String> {
+    match t2 {
+        Type2::IntValue { value, .. } => Some(value.to_string()),
+        Type2::UintValue { value, .. } => Some(value.to_string()),
+        Type2::FloatValue { value, .. } => Some(value.to_string()),
+        Type2::TextValue { value, .. } => Some(text_literal(value)),
+        _ => None,
+    }
+}
+
+fn and(checks: Vec<String>) -> String {
+    let checks = checks
+        .into_iter()
+        .filter(|check| check != "true")
+        .collect::<Vec<_>>();
+    match checks.len() {
+        0 => "true".to_string(),
+        1 => checks.into_iter().next().unwrap(),
+        _ => format!("({})", checks.join(" && ")),
+    }
+}
+
+fn or(checks: Vec<String>) -> String {
+    if checks.iter().any(|check| check == "true") {
+        return "true".to_string();
+    }
+    match checks.len() {
+        1 => checks.into_iter().next().unwrap(),
+        _ => format!("({})", checks.join(" || ")),
+    }
+}
+
+fn as_object(value: &str) -> String {
+    format!("({} as Record<string, unknown>)", value)
+}
+
+fn is_object(value: &str) -> String {
+    format!(
+        "(typeof {} === \"object\" && {} !== null && !Array.isArray({}))",
+        value, value, value
+    )
+}
+
+impl<'o> Guards<'o> {
+    pub(crate) fn new(options: &'o Options, names: &'o Names) -> Guards<'o> {
+        Guards {
+            options,
+            names,
+            generic_params: Vec::new(),
+            depth: 0,
+            named_keys: Vec::new(),
+            rule: String::new(),
+            diagnostics: Vec::new(),
+        }
+    }
+
+    /// Writes the guard of a type rule.
+    pub(crate) fn type_rule(&mut self, tr: &cddl::ast::TypeRule) -> String {
+        self.rule = tr.name.ident.to_string();
+        self.enter_rule(&tr.generic_params);
+        let check = self.type_check(&tr.value, "value");
+        self.exit_rule(tr.name.ident, &tr.generic_params, check)
+    }
+
+    /// Writes the guard of a group rule, which checks the members of an
+    /// object.
+    pub(crate) fn group_rule(&mut self, gr: &cddl::ast::GroupRule) -> String {
+        self.rule = gr.name.ident.to_string();
+        self.enter_rule(&gr.generic_params);
+        let check = and(vec![
+            is_object("value"),
+            self.group_entry_check(&gr.entry, "value"),
+        ]);
+        self.exit_rule(gr.name.ident, &gr.generic_params, check)
+    }
+
+    /// Writes a guard per variant of a discriminated union, which narrows the
+    /// union by its discriminator (e.g. `isMessageSuccess`).
+    ///
+    /// Named variants are resolved with `maps` and groups in inline maps with
+    /// `groups`. The guards are named with [`Names::variants`], given the
+    /// names of the variants guarded so far in `taken`. Also returns a
+    /// message for every variant named differently.
+    pub(crate) fn variant_guards(
+        &self,
+        tr: &cddl::ast::TypeRule,
+        maps: &BTreeMap<String, LiteralMembers>,
+        groups: &dyn Fn(&str) -> Option<LiteralMembers>,
+        taken: &mut HashMap<String, String>,
+    ) -> (String, Vec<String>) {
+        // Parentheses around the choice (e.g. `message = (a / b)`) change
+        // nothing.
+        let mut t = &tr.value;
+        while let [cddl::ast::TypeChoice {
+            type1:
+                Type1 {
+                    type2: Type2::ParenthesizedType { pt, .. },
+                    operator: None,
+                    ..
+                },
+            ..
+        }] = t.type_choices.as_slice()
+        {
+            t = pt;
+        }
+        if tr.generic_params.is_some() || t.type_choices.len() < 2 {
+            return Default::default();
+        }
+        let variants = t
+            .type_choices
+            .iter()
+            .map(|choice| {
+                if choice.type1.operator.is_some() {
+                    return None;
+                }
+                match &choice.type1.type2 {
+                    Type2::Typename {
+                        ident,
+                        generic_args: None,
+                        ..
+                    } if ident.socket.is_none() => maps.get(ident.ident).cloned(),
+                    Type2::Map { group, .. } => literal_members(group, groups),
+                    _ => None,
+                }
+            })
+            .collect::<Option<Vec<_>>>();
+        let Some(variants) = variants else {
+            return Default::default();
+        };
+        let Some(key) = find_discriminator(&variants) else {
+            return Default::default();
+        };
+        let values = variants
+            .iter()
+            .map(|variant| {
+                variant
+                    .iter()
+                    .find(|(other, _)| other == key)
+                    .map(|(_, value)| value.clone())
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+        let (variant_names, collisions) =
+            self.names
+                .variants(tr.name.ident, values.iter().map(String::as_str), taken);
+        let (_, name) = self.names.split_rule(tr.name.ident);
+        let key = to_string_literal(key);
+        let guards = values
+            .iter()
+            .zip(variant_names)
+            .map(|(value, variant)| {
+                format!(
+                    "export function is{}(value: {}): value is Extract<{}, {{ {}: {} }}> {{\nreturn value[{}] === {};\n}}\n",
+                    variant, name, name, key, value, key, value
+                )
+            })
+            .collect();
+        (guards, collisions)
+    }
+
+    fn enter_rule(&mut self, params: &Option<cddl::ast::GenericParams>) {
+        self.generic_params = params
+            .iter()
+            .flat_map(|params| &params.params)
+            .map(|param| param.param.ident.to_string())
+            .collect();
+    }
+
+    fn exit_rule(
+        &mut self,
+        ident: &str,
+        params: &Option<cddl::ast::GenericParams>,
+        check: String,
+    ) -> String {
+        let (_, name) = self.names.split_rule(ident);
+        let mut guard = format!("export function is{}", name);
+        let mut type_name = name;
+        if !self.generic_params.is_empty() {
+            let params = params.as_ref().unwrap();
+            let type_params = params
+                .params
+                .iter()
+                .map(|param| self.names.rule(param.param.ident))
+                .collect::<Vec<_>>()
+                .join(", ");
+            guard.push_str(&format!("<{}>(value: unknown", type_params));
+            for param in &params.params {
+                guard.push_str(&format!(
+                    ", is{}: (value: unknown) => boolean",
+                    self.names.rule(param.param.ident)
+                ));
+            }
+            type_name.push_str(&format!("<{}>", type_params));
+        } else {
+            guard.push_str("(value: unknown");
+        }
+        guard.push_str(&format!(
+            "): value is {} {{\nreturn {};\n}}\n",
+            type_name, check
+        ));
+        self.generic_params.clear();
+        guard
+    }
+
+    /// Returns the name of the guard of a rule, e.g. `BrowsingContext.isInfo`
+    /// for `browsingContext.Info`.
+    fn guard_name(&self, ident: &str) -> String {
+        let (mut namespaces, name) = self.names.split_rule(ident);
+        namespaces.push(format!("is{}", name));
+        namespaces.join(".")
+    }
+
+    fn named_keys(&self, choice: &GroupChoice) -> Vec<String> {
+        choice
+            .group_entries
+            .iter()
+            .filter_map(|(entry, _)| match entry {
+                GroupEntry::ValueMemberKey { ge, .. } => match &ge.member_key {
+                    Some(
+                        mk @ (MemberKey::Bareword { .. }
+                        | MemberKey::Value {
+                            value: cddl::token::Value::TEXT(_),
+                            ..
+                        }),
+                    ) => member_key_name(mk),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn type_check(&mut self, t: &Type, value: &str) -> String {
+        or(t.type_choices
+            .iter()
+            .map(|choice| self.type1_check(&choice.type1, value))
+            .collect())
+    }
+
+    fn type1_check(&mut self, t1: &Type1, value: &str) -> String {
+        let Some(operator) = &t1.operator else {
+            return self.type2_check(&t1.type2, value);
+        };
+        match &operator.operator {
+            cddl::ast::RangeCtlOp::RangeOp { is_inclusive, .. } => {
+                let (Some(lower), Some(upper)) = (literal(&t1.type2), literal(&operator.type2))
+                else {
+                    return "true".to_string();
+                };
+                let is_integer =
+                    matches!(t1.type2, Type2::IntValue { .. } | Type2::UintValue { .. })
+                        && matches!(
+                            operator.type2,
+                            Type2::IntValue { .. } | Type2::UintValue { .. }
+                        );
+                let (kind, numeric) =
+                    if self.options.profile == Profile::Cbor && !crate::util::is_safe_range(t1) {
+                        (
+                            format!(
+                                "(typeof {} === \"bigint\" || Number.isInteger({}))",
+                                value, value
+                            ),
+                            format!("({} as number | bigint)", value),
+                        )
+                    } else if is_integer {
+                        (
+                            format!("Number.isInteger({})", value),
+                            format!("({} as number)", value),
+                        )
+                    } else {
+                        (
+                            format!("typeof {} === \"number\"", value),
+                            format!("({} as number)", value),
+                        )
+                    };
+                and(vec![
+                    kind,
+                    format!("{} >= {}", numeric, lower),
+                    format!(
+                        "{} {} {}",
+                        numeric,
+                        if *is_inclusive { "<=" } else { "<" },
+                        upper
+                    ),
+                ])
+            }
+            cddl::ast::RangeCtlOp::CtlOp { ctrl, .. } => {
+                let base = self.type2_check(&t1.type2, value);
+                let constraint = match ctrl {
+                    cddl::token::ControlOperator::SIZE => match literal(&operator.type2) {
+                        Some(size) if self.has_length(&t1.type2) => {
+                            format!("({} as {{length: number}}).length === {}", value, size)
+                        }
+                        _ => "true".to_string(),
+                    },
+                    cddl::token::ControlOperator::REGEXP | cddl::token::ControlOperator::PCRE => {
+                        match &operator.type2 {
+                            Type2::TextValue { value: pattern, .. } => {
+                                let source = if *ctrl == cddl::token::ControlOperator::REGEXP {
+                                    regexp::to_ecmascript(pattern)
+                                        .map(|source| to_string_literal(&source))
+                                } else {
+                                    Ok(text_literal(pattern))
+                                };
+                                match source {
+                                    Ok(source) => {
+                                        format!("new RegExp({}).test({} as string)", source, value)
+                                    }
+                                    Err(reason) => {
+                                        self.diagnostics.push(format!(
+                                            "In `{}`, the guard does not check the pattern `{}`, \
+                                            as {}.",
+                                            self.rule, pattern, reason
+                                        ));
+                                        "true".to_string()
+                                    }
+                                }
+                            }
+                            _ => "true".to_string(),
+                        }
+                    }
+                    cddl::token::ControlOperator::LT
+                    | cddl::token::ControlOperator::LE
+                    | cddl::token::ControlOperator::GT
+                    | cddl::token::ControlOperator::GE
+                    | cddl::token::ControlOperator::EQ
+                    | cddl::token::ControlOperator::NE => match literal(&operator.type2) {
+                        Some(bound) => {
+                            let symbol = match ctrl {
+                                cddl::token::ControlOperator::LT => "<",
+                                cddl::token::ControlOperator::LE => "<=",
+                                cddl::token::ControlOperator::GT => ">",
+                                cddl::token::ControlOperator::GE => ">=",
+                                cddl::token::ControlOperator::EQ => "===",
+                                _ => "!==",
+                            };
+                            format!("({} as number) {} {}", value, symbol, bound)
+                        }
+                        None => "true".to_string(),
+                    },
+                    cddl::token::ControlOperator::AND | cddl::token::ControlOperator::WITHIN => {
+                        self.type2_check(&operator.type2, value)
+                    }
+                    _ => "true".to_string(),
+                };
+                and(vec![base, constraint])
+            }
+        }
+    }
+
+    /// Whether `.size` is the length of the value rather than its size in
+    /// bytes.
+    fn has_length(&self, t2: &Type2) -> bool {
+        match t2 {
+            Type2::Typename { ident, .. } => match ident.ident {
+                "tstr" | "text" => true,
+                "bstr" | "bytes" => self.options.profile == Profile::Cbor,
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    /// Checks that `value` has the bytes of a byte string: their base64url in
+    /// JSON, or a `Uint8Array` of them in CBOR.
+    fn bytes_check(&mut self, bytes: &ByteValue, value: &str) -> String {
+        let Some(decoded) = byte_value(bytes) else {
+            self.diagnostics.push(format!(
+                "In `{}`, `{}` is not a valid byte string; the guard checks bytes.",
+                self.rule, bytes
+            ));
+            return self.prelude_check("bytes", value).unwrap();
+        };
+        match self.options.profile {
+            Profile::Json => format!(
+                "{} === {}",
+                value,
+                to_string_literal(to_base64url(&decoded))
+            ),
+            Profile::Cbor => {
+                let bytes = format!("({} as Uint8Array)", value);
+                format!(
+                    "({} instanceof Uint8Array && {}.length === {} && {:?}.every((byte, index) => {}[index] === byte))",
+                    value,
+                    bytes,
+                    decoded.len(),
+                    decoded,
+                    bytes
+                )
+            }
+        }
+    }
+
+    fn type2_check(&mut self, t2: &Type2, value: &str) -> String {
+        if let Some(literal) = literal(t2) {
+            return format!("{} === {}", value, literal);
+        }
+        match t2 {
+            Type2::UTF8ByteString { value: bytes, .. } => {
+                self.bytes_check(&ByteValue::UTF8(bytes.clone()), value)
+            }
+            Type2::B16ByteString { value: bytes, .. } => {
+                self.bytes_check(&ByteValue::B16(bytes.clone()), value)
+            }
+            Type2::B64ByteString { value: bytes, .. } => {
+                self.bytes_check(&ByteValue::B64(bytes.clone()), value)
+            }
+            Type2::Typename {
+                ident,
+                generic_args,
+                ..
+            } => {
+                if ident.socket.is_some() {
+                    return "true".to_string();
+                }
+                if self.generic_params.iter().any(|param| param == ident.ident) {
+                    return format!("is{}({})", self.names.rule(ident.ident), value);
+                }
+                if let Some(check) = self.prelude_check(ident.ident, value) {
+                    return check;
+                }
+                let mut check = format!("{}({}", self.guard_name(ident.ident), value);
+                for arg in generic_args.iter().flat_map(|args| &args.args) {
+                    let arg = self.type1_check(&arg.arg, "value");
+                    check.push_str(&format!(", (value: unknown) => {}", arg));
+                }
+                check.push(')');
+                check
+            }
+            Type2::ParenthesizedType { pt, .. } => self.type_check(pt, value),
+            Type2::Map { group, .. } => self.map_check(group, value),
+            Type2::Array { group, .. } => self.array_check(group, value),
+            Type2::TaggedData { tag, t, .. } => self.tagged_data_check(tag, Some(t), value),
+            Type2::DataMajorType { mt, constraint, .. } => {
+                let constraint_value = constraint
+                    .as_ref()
+                    .and_then(|constraint| constraint.as_literal());
+                if let Some(prelude) = major_type_prelude(*mt, constraint_value) {
+                    return self.prelude_check(prelude, value).unwrap();
+                }
+                match mt {
+                    4 => format!("Array.isArray({})", value),
+                    5 => is_object(value),
+                    6 => self.tagged_data_check(constraint, None, value),
+                    7 => match constraint_value {
+                        Some(_) => "true".to_string(),
+                        None => or(vec![
+                            format!("typeof {} === \"boolean\"", value),
+                            format!("{} === null", value),
+                            format!("{} === undefined", value),
+                            format!("typeof {} === \"number\"", value),
+                        ]),
+                    },
+                    _ => "true".to_string(),
+                }
+            }
+            _ => "true".to_string(),
+        }
+    }
+
+    fn prelude_check(&self, ident: &str, value: &str) -> Option<String> {
+        let cbor = self.options.profile == Profile::Cbor;
+        let integer = |sign: &str| {
+            let (kind, numeric) = if cbor {
+                (
+                    format!(
+                        "(typeof {} === \"bigint\" || Number.isInteger({}))",
+                        value, value
+                    ),
+                    format!("({} as number | bigint)", value),
+                )
+            } else {
+                (
+                    format!("Number.isInteger({})", value),
+                    format!("({} as number)", value),
+                )
+            };
+            match sign {
+                "" => kind,
+                sign => and(vec![kind, format!("{} {} 0", numeric, sign)]),
+            }
+        };
+        let bignum = |sign: &str| {
+            if cbor {
+                match sign {
+                    "" => format!("typeof {} === \"bigint\"", value),
+                    sign => and(vec![
+                        format!("typeof {} === \"bigint\"", value),
+                        format!("({} as bigint) {} 0n", value, sign),
+                    ]),
+                }
+            } else {
+                match sign {
+                    "" => format!("Number.isInteger({})", value),
+                    sign => and(vec![
+                        format!("Number.isInteger({})", value),
+                        format!("({} as number) {} 0", value, sign),
+                    ]),
+                }
+            }
+        };
+        Some(match ident {
+            "bool" => format!("typeof {} === \"boolean\"", value),
+            "uint" => integer(">="),
+            "nint" => integer("<"),
+            "int" => integer(""),
+            "float16" | "float32" | "float64" | "float16-32" | "float32-64" | "float"
+            | "number" | "time" => format!("typeof {} === \"number\"", value),
+            "biguint" => bignum(">="),
+            "bignint" => bignum("<"),
+            "bigint" => bignum(""),
+            "bstr" | "bytes" | "encoded-cbor" => {
+                if cbor {
+                    format!("{} instanceof Uint8Array", value)
+                } else {
+                    format!("typeof {} === \"string\"", value)
+                }
+            }
+            "tstr" | "text" | "tdate" | "b64url" | "b64legacy" | "mime-message" => {
+                format!("typeof {} === \"string\"", value)
+            }
+            "any" | "cbor-any" => "true".to_string(),
+            "nil" | "null" => format!("{} === null", value),
+            "true" => format!("{} === true", value),
+            "false" => format!("{} === false", value),
+            "undefined" => format!("{} === undefined", value),
+            // URIs are decoded as text, not as `URL` objects.
+            "uri" => format!(
+                "(typeof {} === \"string\" && URL.canParse({}))",
+                value, value
+            ),
+            "regexp" => format!("{} instanceof RegExp", value),
+            _ => return None,
+        })
+    }
+
+    fn tagged_data_check(
+        &mut self,
+        tag: &Option<cddl::token::TagConstraint>,
+        t: Option<&Type>,
+        value: &str,
+    ) -> String {
+        let tag = tag.as_ref().and_then(|tag| tag.as_literal());
+        match well_known_tag(tag, t) {
+            Some(WellKnownTag::Prelude(prelude)) => {
+                return self.prelude_check(prelude, value).unwrap()
+            }
+            Some(WellKnownTag::Content(t)) => return self.type_check(t, value),
+            None => {}
+        }
+        if !self.options.cbor_tags {
+            return match t {
+                Some(t) => self.type_check(t, value),
+                None => "true".to_string(),
+            };
+        }
+        let object = as_object(value);
+        and(vec![
+            is_object(value),
+            match tag {
+                Some(tag) => format!("{}[\"tag\"] === {}", object, tag),
+                None => format!("Number.isInteger({}[\"tag\"])", object),
+            },
+            match t {
+                Some(t) => self.type_check(t, &format!("{}[\"value\"]", object)),
+                None => "true".to_string(),
+            },
+        ])
+    }
+
+    fn map_check(&mut self, group: &Group, value: &str) -> String {
+        and(vec![
+            is_object(value),
+            or(group
+                .group_choices
+                .iter()
+                .map(|choice| self.group_choice_check(choice, value))
+                .collect()),
+        ])
+    }
+
+    fn group_choice_check(&mut self, choice: &GroupChoice, value: &str) -> String {
+        if choice.group_entries.is_empty() {
+            return format!("Object.keys({}).length === 0", as_object(value));
+        }
+        let keys = self.named_keys(choice);
+        let named_keys = std::mem::replace(&mut self.named_keys, keys);
+        let check = and(choice
+            .group_entries
+            .iter()
+            .map(|(entry, _)| self.group_entry_check(entry, value))
+            .collect());
+        self.named_keys = named_keys;
+        check
+    }
+
+    fn group_entry_check(&mut self, entry: &GroupEntry, value: &str) -> String {
+        let object = as_object(value);
+        match entry {
+            GroupEntry::ValueMemberKey { ge, .. } => {
+                let (lower, upper) = calculate_occurrence(&ge.occur);
+                let key = match &ge.member_key {
+                    Some(MemberKey::Value {
+                        value: value @ cddl::token::Value::BYTE(_),
+                        ..
+                    }) => {
+                        self.diagnostics.push(format!(
+                            "In `{}`, the guard does not check the member `{}`, as byte \
+                            strings are no object keys.",
+                            self.rule, value
+                        ));
+                        return "true".to_string();
+                    }
+                    Some(mk @ (MemberKey::Bareword { .. } | MemberKey::Value { .. })) => {
+                        member_key_name(mk).unwrap()
+                    }
+                    Some(MemberKey::Type1 { t1, .. }) => {
+                        // Computed keys constrain every member whose key
+                        // matches.
+                        self.depth += 1;
+                        let key = format!("key{}", self.depth);
+                        let item = format!("item{}", self.depth);
+                        let key_check = self.type1_check(t1, &key);
+                        let item_check = self.type_check(&ge.entry_type, &item);
+                        self.depth -= 1;
+                        if item_check == "true" {
+                            return "true".to_string();
+                        }
+                        let mut check = format!("!({}) || {}", key_check, item_check);
+                        if !self.named_keys.is_empty() {
+                            let named = self
+                                .named_keys
+                                .iter()
+                                .map(to_string_literal)
+                                .collect::<Vec<_>>()
+                                .join(", ");
+                            check = format!("[{}].includes({}) || {}", named, key, check);
+                        }
+                        return format!(
+                            "Object.entries({}).every(([{}, {}]) => {})",
+                            object, key, item, check
+                        );
+                    }
+                    _ => return "true".to_string(),
+                };
+                let key = to_string_literal(key);
+                let member = format!("{}[{}]", object, key);
+                let check = self.type_check(&ge.entry_type, &member);
+                if lower == 0 && upper > 0 {
+                    or(vec![format!("{} === undefined", member), check])
+                } else {
+                    and(vec![format!("{} in {}", key, object), check])
+                }
+            }
+            GroupEntry::TypeGroupname { ge, .. } => {
+                if matches!(calculate_occurrence(&ge.occur), (0, max) if max > 0) {
+                    return "true".to_string();
+                }
+                self.type2_check(
+                    &Type2::Typename {
+                        ident: ge.name.clone(),
+                        generic_args: ge.generic_args.clone(),
+                        span: Default::default(),
+                    },
+                    value,
+                )
+            }
+            GroupEntry::InlineGroup { occur, group, .. } => {
+                if matches!(calculate_occurrence(occur), (0, max) if max > 0) {
+                    return "true".to_string();
+                }
+                or(group
+                    .group_choices
+                    .iter()
+                    .map(|choice| self.group_choice_check(choice, value))
+                    .collect())
+            }
+        }
+    }
+
+    fn array_check(&mut self, group: &Group, value: &str) -> String {
+        let array = format!("({} as unknown[])", value);
+        and(vec![
+            format!("Array.isArray({})", value),
+            or(group
+                .group_choices
+                .iter()
+                .map(|choice| self.array_choice_check(choice, &array))
+                .collect()),
+        ])
+    }
+
+    /// Checks arrays made of fixed elements optionally followed by a single
+    /// repeated element.
+    fn array_choice_check(&mut self, choice: &GroupChoice, array: &str) -> String {
+        let mut checks = Vec::new();
+        let mut index = 0;
+        for (position, (entry, _)) in choice.group_entries.iter().enumerate() {
+            let GroupEntry::ValueMemberKey { ge, .. } = entry else {
+                return "true".to_string();
+            };
+            let (lower, upper) = calculate_occurrence(&ge.occur);
+            if lower == upper {
+                for _ in 0..lower {
+                    checks.push(self.type_check(&ge.entry_type, &format!("{}[{}]", array, index)));
+                    index += 1;
+                }
+                continue;
+            }
+            if position + 1 != choice.group_entries.len() {
+                return "true".to_string();
+            }
+            if lower > 0 {
+                checks.push(format!("{}.length >= {}", array, index + lower));
+            }
+            if upper != usize::MAX {
+                checks.push(format!("{}.length <= {}", array, index + upper));
+            }
+            self.depth += 1;
+            let item = format!("item{}", self.depth);
+            let item_check = self.type_check(&ge.entry_type, &item);
+            self.depth -= 1;
+            if item_check != "true" {
+                let items = match index {
+                    0 => array.to_string(),
+                    index => format!("{}.slice({})", array, index),
+                };
+                checks.push(format!("{}.every(({}) => {})", items, item, item_check));
+            }
+            return and(checks);
+        }
+        checks.insert(0, format!("{}.length === {}", array, index));
+        and(checks)
+    }
+}
@@ -0,0 +1,434 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Conversion of simple regular expressions into TypeScript template literal
+//! types, and of XSD regular expressions into ECMAScript ones.
+//!
+//! The generated types are always at least as permissive as the pattern, so a
+//! string matching the pattern is never rejected by the type checker. Patterns
+//! that cannot be expressed this way yield `None`.
+
+use std::iter::Peekable;
+use std::str::Chars;
+
+use crate::util::unescape_text;
+
+const MAX_TEMPLATES: usize = 1 << 4;
+const MAX_CLASS_CHARS: usize = 1 << 3;
+
+/// The characters XML names start with, as matched by `\i`, within the Basic
+/// Multilingual Plane.
+const NAME_START_CHARS: &str =
+    ":A-Z_a-z\\u00C0-\\u00D6\\u00D8-\\u00F6\\u00F8-\\u02FF\\u0370-\\u037D\
+    \\u037F-\\u1FFF\\u200C-\\u200D\\u2070-\\u218F\\u2C00-\\u2FEF\\u3001-\\uD7FF\\uF900-\\uFDCF\
+    \\uFDF0-\\uFFFD";
+/// The other characters of XML names, matched by `\c` along with
+/// [`NAME_START_CHARS`].
+const NAME_CHARS: &str = "\\-.0-9\\u00B7\\u0300-\\u036F\\u203F-\\u2040";
+
+#[derive(Clone, PartialEq)]
+enum Piece {
+    Text(String),
+    Number,
+    String,
+}
+
+type Template = Vec<Piece>;
+
+type Sequence = Vec<(Atom, (usize, usize))>;
+
+enum Atom {
+    Literal(char),
+    Class(Vec<char>),
+    Digit,
+    Any,
+    Group(Vec<Sequence>),
+}
+
+/// Converts a (JSON-escaped) CDDL regular expression into a union of template
+/// literal types.
+///
+/// `xsd` should be `true` for `.regexp` (XSD syntax, implicitly anchored,
+/// where `^` and `$` are plain characters) and `false` for `.pcre`.
+pub(crate) fn to_template_literal(pattern: &str, xsd: bool) -> Option<String> {
+    let pattern = unescape_text(pattern);
+    let mut pattern = pattern.as_str();
+    let mut prefix = !xsd;
+    let mut suffix = !xsd;
+    if !xsd {
+        if let Some(rest) = pattern.strip_prefix('^') {
+            pattern = rest;
+            prefix = false;
+        }
+        if let Some(rest) = pattern.strip_suffix('$') {
+            if !rest.ends_with('\\') {
+                pattern = rest;
+                suffix = false;
+            }
+        }
+    }
+
+    let mut chars = pattern.chars().peekable();
+    let alternatives = parse_alternatives(&mut chars, xsd)?;
+    if chars.next().is_some() {
+        return None;
+    }
+
+    let mut templates = expand_alternatives(&alternatives)?;
+    for template in &mut templates {
+        if prefix {
+            template.insert(0, Piece::String);
+        }
+        if suffix {
+            template.push(Piece::String);
+        }
+        normalize(template);
+    }
+    templates.dedup();
+    if templates
+        .iter()
+        .any(|template| *template == [Piece::String])
+    {
+        return None;
+    }
+
+    Some(
+        templates
+            .iter()
+            .map(print_template)
+            .collect::<Vec<_>>()
+            .join(" | "),
+    )
+}
+
+/// Converts a (JSON-escaped) CDDL `.regexp` pattern, in XSD syntax, into the
+/// source of an anchored ECMAScript regular expression.
+///
+/// `^` and `$` are plain characters in XSD, and `\i` and `\c` (XML name
+/// characters) are expanded. Character class subtraction and `\p` escapes
+/// have no equivalent without the `u` flag and are an error.
+pub(crate) fn to_ecmascript(pattern: &str) -> Result<String, String> {
+    let pattern = unescape_text(pattern);
+    let mut source = String::from("^(?:");
+    let mut in_class = false;
+    let mut chars = pattern.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => match chars.next() {
+                Some(escape @ ('i' | 'c')) => {
+                    let class = match escape {
+                        'i' => NAME_START_CHARS.to_string(),
+                        _ => format!("{}{}", NAME_START_CHARS, NAME_CHARS),
+                    };
+                    if in_class {
+                        source.push_str(&class);
+                    } else {
+                        source.push_str(&format!("[{}]", class));
+                    }
+                }
+                Some(escape @ ('I' | 'C')) if !in_class => {
+                    let class = match escape {
+                        'I' => NAME_START_CHARS.to_string(),
+                        _ => format!("{}{}", NAME_START_CHARS, NAME_CHARS),
+                    };
+                    source.push_str(&format!("[^{}]", class));
+                }
+                Some(escape @ ('I' | 'C')) => {
+                    return Err(format!("`\\{}` cannot be negated in a class", escape))
+                }
+                Some(escape @ ('p' | 'P')) => {
+                    return Err(format!("`\\{}` has no ECMAScript equivalent", escape))
+                }
+                Some(escape) => {
+                    source.push('\\');
+                    source.push(escape);
+                }
+                None => return Err("it ends with `\\`".to_string()),
+            },
+            '[' if in_class => {
+                return Err("class subtraction has no ECMAScript equivalent".to_string())
+            }
+            '[' => {
+                in_class = true;
+                source.push('[');
+                if chars.peek() == Some(&'^') {
+                    source.push('^');
+                    chars.next();
+                }
+            }
+            ']' if in_class => {
+                in_class = false;
+                source.push(']');
+            }
+            '^' | '$' if !in_class => {
+                source.push('\\');
+                source.push(ch);
+            }
+            ch => source.push(ch),
+        }
+    }
+    source.push_str(")$");
+    Ok(source)
+}
+
+fn parse_alternatives(chars: &mut Peekable<Chars<'_>>, xsd: bool) -> Option<Vec<Sequence>> {
+    let mut alternatives = vec![parse_sequence(chars, xsd)?];
+    while chars.peek() == Some(&'|') {
+        chars.next();
+        alternatives.push(parse_sequence(chars, xsd)?);
+    }
+    Some(alternatives)
+}
+
+fn parse_sequence(chars: &mut Peekable<Chars<'_>>, xsd: bool) -> Option<Sequence> {
+    let mut sequence = Vec::new();
+    while let Some(&ch) = chars.peek() {
+        let atom = match ch {
+            '|' | ')' => break,
+            '(' => {
+                chars.next();
+                if chars.peek() == Some(&'?') {
+                    chars.next();
+                    if chars.next() != Some(':') {
+                        return None;
+                    }
+                }
+                let group = parse_alternatives(chars, xsd)?;
+                if chars.next() != Some(')') {
+                    return None;
+                }
+                Atom::Group(group)
+            }
+            '[' => {
+                chars.next();
+                parse_class(chars)?
+            }
+            '\\' => {
+                chars.next();
+                parse_escape(chars.next()?)?
+            }
+            '.' => {
+                chars.next();
+                Atom::Any
+            }
+            '^' | '$' if !xsd => return None,
+            '*' | '+' | '?' | '{' => return None,
+            ch => {
+                chars.next();
+                Atom::Literal(ch)
+            }
+        };
+        sequence.push((atom, parse_quantifier(chars)?));
+    }
+    Some(sequence)
+}
+
+fn parse_escape(ch: char) -> Option<Atom> {
+    match ch {
+        'd' => Some(Atom::Digit),
+        'D' | 'w' | 'W' | 's' | 'S' | 'i' | 'I' | 'c' | 'C' | 'p' | 'P' => Some(Atom::Any),
+        'n' => Some(Atom::Literal('\n')),
+        'r' => Some(Atom::Literal('\r')),
+        't' => Some(Atom::Literal('\t')),
+        ch if ch.is_ascii_punctuation() => Some(Atom::Literal(ch)),
+        _ => None,
+    }
+}
+
+fn parse_class(chars: &mut Peekable<Chars<'_>>) -> Option<Atom> {
+    let negated = chars.peek() == Some(&'^');
+    if negated {
+        chars.next();
+    }
+    let mut members = Vec::new();
+    let mut has_range = false;
+    loop {
+        let ch = match chars.next()? {
+            ']' if !members.is_empty() || has_range => break,
+            '\\' => match parse_escape(chars.next()?)? {
+                Atom::Literal(ch) => ch,
+                _ => {
+                    has_range = true;
+                    continue;
+                }
+            },
+            ch => ch,
+        };
+        if chars.peek() == Some(&'-') {
+            chars.next();
+            if chars.peek() == Some(&']') {
+                members.push(ch);
+                members.push('-');
+                continue;
+            }
+            let end = chars.next()?;
+            if ch == '0' && end == '9' && members.is_empty() && chars.peek() == Some(&']') {
+                chars.next();
+                return Some(if negated { Atom::Any } else { Atom::Digit });
+            }
+            has_range = true;
+        } else {
+            members.push(ch);
+        }
+    }
+    if negated || has_range || members.len() > MAX_CLASS_CHARS {
+        Some(Atom::Any)
+    } else {
+        Some(Atom::Class(members))
+    }
+}
+
+fn parse_quantifier(chars: &mut Peekable<Chars<'_>>) -> Option<(usize, usize)> {
+    let occurrence = match chars.peek() {
+        Some('?') => (0, 1),
+        Some('*') => (0, usize::MAX),
+        Some('+') => (1, usize::MAX),
+        Some('{') => {
+            chars.next();
+            let mut bounds = String::new();
+            loop {
+                match chars.next()? {
+                    '}' => break,
+                    ch => bounds.push(ch),
+                }
+            }
+            let occurrence = match bounds.split_once(',') {
+                Some((lower, "")) => (lower.parse().ok()?, usize::MAX),
+                Some((lower, upper)) => (lower.parse().ok()?, upper.parse().ok()?),
+                None => {
+                    let exact = bounds.parse().ok()?;
+                    (exact, exact)
+                }
+            };
+            // Lazy quantifiers match the same strings.
+            if chars.peek() == Some(&'?') {
+                chars.next();
+            }
+            return Some(occurrence);
+        }
+        _ => return Some((1, 1)),
+    };
+    chars.next();
+    if chars.peek() == Some(&'?') {
+        chars.next();
+    }
+    Some(occurrence)
+}
+
+fn expand_alternatives(alternatives: &[Sequence]) -> Option<Vec<Template>> {
+    let mut templates = Vec::new();
+    for sequence in alternatives {
+        let mut sequence_templates = vec![Template::new()];
+        for (atom, occurrence) in sequence {
+            sequence_templates = concat(&sequence_templates, &expand_atom(atom, *occurrence)?)?;
+        }
+        templates.extend(sequence_templates);
+    }
+    if templates.len() > MAX_TEMPLATES {
+        return None;
+    }
+    Some(templates)
+}
+
+fn expand_atom(atom: &Atom, (lower, upper): (usize, usize)) -> Option<Vec<Template>> {
+    let once = match atom {
+        Atom::Any => return Some(vec![vec![Piece::String]]),
+        Atom::Digit => {
+            return Some(match lower {
+                0 => vec![vec![], vec![Piece::Number]],
+                _ => vec![vec![Piece::Number]],
+            })
+        }
+        Atom::Literal(ch) => vec![vec![Piece::Text(ch.to_string())]],
+        Atom::Class(members) => members
+            .iter()
+            .map(|ch| vec![Piece::Text(ch.to_string())])
+            .collect(),
+        Atom::Group(alternatives) => expand_alternatives(alternatives)?,
+    };
+    if upper == usize::MAX || upper > MAX_TEMPLATES {
+        // Unbounded repetitions cannot be expressed exactly, so fall back to
+        // the most permissive template.
+        return Some(match lower {
+            0 => vec![vec![], vec![Piece::String]],
+            _ => vec![vec![Piece::String]],
+        });
+    }
+    let mut templates = Vec::new();
+    let mut repeated = vec![Template::new()];
+    for count in 0..=upper {
+        if count >= lower {
+            templates.extend(repeated.iter().cloned());
+        }
+        if count < upper {
+            repeated = concat(&repeated, &once)?;
+        }
+    }
+    if templates.len() > MAX_TEMPLATES {
+        return None;
+    }
+    Some(templates)
+}
+
+fn concat(lhs: &[Template], rhs: &[Template]) -> Option<Vec<Template>> {
+    if lhs.len() * rhs.len() > MAX_TEMPLATES {
+        return None;
+    }
+    Some(
+        lhs.iter()
+            .flat_map(|lhs| {
+                rhs.iter()
+                    .map(move |rhs| lhs.iter().chain(rhs.iter()).cloned().collect())
+            })
+            .collect(),
+    )
+}
+
+fn normalize(template: &mut Template) {
+    let mut normalized: Template = Vec::with_capacity(template.len());
+    for piece in template.drain(..) {
+        match (normalized.last_mut(), piece) {
+            (Some(Piece::Text(last)), Piece::Text(text)) => last.push_str(&text),
+            (Some(Piece::String), Piece::String) => {}
+            (_, piece) => normalized.push(piece),
+        }
+    }
+    *template = normalized;
+}
+
+fn print_template(template: &Template) -> String {
+    let mut result = String::from("`");
+    for piece in template {
+        match piece {
+            Piece::Text(text) => {
+                for ch in text.chars() {
+                    match ch {
+                        '`' | '\\' | '$' => {
+                            result.push('\\');
+                            result.push(ch);
+                        }
+                        '\n' => result.push_str("\\n"),
+                        '\r' => result.push_str("\\r"),
+                        '\t' => result.push_str("\\t"),
+                        ch => result.push(ch),
+                    }
+                }
+            }
+            Piece::Number => result.push_str("${number}"),
+            Piece::String => result.push_str("${string}"),
+        }
+    }
+    result.push('`');
+    result
+}
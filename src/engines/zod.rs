@@ -14,20 +14,54 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::io::Write;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::Write,
+};
 
-use cddl::{ast::Occurrence, visitor::Visitor, Error};
+use cddl::{
+    ast::{Occurrence, CDDL},
+    token::SocketPlug,
+    visitor::Visitor,
+    Error,
+};
 
-use crate::util::{split_namespaced, to_namespaced};
+use crate::{
+    amendments::merge_amendments,
+    docs::Docs,
+    engines::typescript::regexp,
+    generator::{diagnostics, with_instances, Diagnostic, Generator},
+    naming::Names,
+    options::{Extensibility, Options, Profile},
+    printer::Printer,
+    unwrap::{choice_enums, unwrap},
+    util::{
+        byte_value, catch_all_group_type, catch_all_type, find_discriminator, is_primitive_alias,
+        is_safe_range, literal_members, major_type_prelude, map_literals, member_key_name,
+        plain_groups, sockets, text_literal, to_base64url, to_camelcase, to_pascalcase,
+        to_string_literal, well_known_tag, LiteralMembers, WellKnownTag,
+    },
+    vectors::{array_groups, array_lengths, group_length, Lengths},
+};
 
 const MAX_ARRAYS: usize = 1 << 3;
 
-#[repr(packed)]
+/// Names in scope of every schema, which parameters must not shadow.
+const RESERVED: &[&str] = &["z"];
+
 struct GroupChoiceContext {
     in_object: bool,
     is_first: bool,
+    is_last: bool,
     in_record: bool,
     in_and: bool,
+    /// Schema of the members other than the named ones, if any.
+    catch_all: Option<String>,
+    /// Whether the object rejects unknown members.
+    is_strict: bool,
+    /// Keys of the members written in the object that are renamed, with their
+    /// new names.
+    renamed: Vec<(String, String)>,
 }
 
 #[derive(Copy, Clone)]
@@ -42,10 +76,35 @@ struct Type1Context {
 }
 
 struct PostambleOptions {
-    #[cfg(feature = "vector_groups")]
     print_flatten: bool,
 }
 
+/// The [`Generator`] of Zod schemas.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Zod;
+
+impl Generator for Zod {
+    fn generate(
+        &self,
+        cddl: &CDDL,
+        source: &str,
+        options: &Options,
+        out: &mut dyn Write,
+    ) -> anyhow::Result<Vec<Diagnostic>> {
+        with_instances(cddl, options, |cddl| {
+            let mut engine = Engine::with_writers(out, Vec::new())
+                .with_options(options.clone())
+                .with_source(source);
+            engine.print_preamble();
+            engine.visit_cddl(cddl)?;
+            engine.print_postamble();
+            let (out, stderr) = engine.into_writers();
+            out.flush()?;
+            Ok(diagnostics(&stderr))
+        })
+    }
+}
+
 pub struct Engine<Stdout, Stderr>
 where
     Stdout: Write,
@@ -53,10 +112,37 @@ where
 {
     nested_group_choices: Vec<GroupChoiceContext>,
     nested_type1: Vec<Type1Context>,
-    #[allow(dead_code)]
     postamble_options: PostambleOptions,
-    stdout: Stdout,
-    #[allow(dead_code)]
+    options: Options,
+    /// Referenced or defined sockets, keyed by name.
+    sockets: BTreeMap<String, SocketPlug>,
+    defined_sockets: BTreeSet<String>,
+    /// Parameters of the generic rule being emitted.
+    generic_params: Vec<String>,
+    /// Literal members of the rules that are plain maps, keyed by rule name.
+    map_literals: BTreeMap<String, LiteralMembers>,
+    /// Literal members of the group rules that are plain objects, keyed by
+    /// rule name. These are spread into the maps that reference them.
+    plain_groups: BTreeMap<String, LiteralMembers>,
+    /// Value schemas of the group rules made of a single catch-all member,
+    /// keyed by rule name.
+    catch_all_groups: BTreeMap<String, String>,
+    /// Members of the rules that are a choice from a group of literal values,
+    /// keyed by rule name.
+    enums: BTreeMap<String, LiteralMembers>,
+    /// Group rules used as elements of arrays, which have a `Vector` schema
+    /// if `vector_groups` is set.
+    array_groups: BTreeSet<String>,
+    /// Numbers of elements of the `array_groups`.
+    array_lengths: Lengths,
+    /// Whether the group being visited is the body of a map.
+    in_map: bool,
+    /// Comments of the source, which document the rules and members.
+    docs: Docs,
+    /// CDDL name of the rule being emitted.
+    rule: String,
+    names: Names,
+    stdout: Printer<Stdout>,
     stderr: Stderr,
 }
 
@@ -80,15 +166,39 @@ impl<'a, 'b: 'a, 'c, Stdout: Write, Stderr: Write> Engine<Stdout, Stderr> {
             nested_group_choices: Vec::new(),
             nested_type1: Vec::new(),
             postamble_options: PostambleOptions {
-                #[cfg(feature = "vector_groups")]
                 print_flatten: false,
             },
-            stdout,
+            options: Options::default(),
+            sockets: BTreeMap::new(),
+            defined_sockets: BTreeSet::new(),
+            generic_params: Vec::new(),
+            map_literals: BTreeMap::new(),
+            plain_groups: BTreeMap::new(),
+            catch_all_groups: BTreeMap::new(),
+            enums: BTreeMap::new(),
+            array_groups: BTreeSet::new(),
+            array_lengths: Lengths::new(),
+            in_map: false,
+            docs: Docs::default(),
+            rule: String::new(),
+            names: Names::default(),
+            stdout: Printer::new(stdout),
             stderr,
         }
     }
+    pub fn with_options(mut self, options: Options) -> Engine<Stdout, Stderr> {
+        self.stdout.set_layout(options.layout.clone());
+        self.options = options;
+        self
+    }
+    /// Documents the output with the comments of `source`, the text the AST
+    /// given to `visit_cddl` was parsed from.
+    pub fn with_source(mut self, source: &str) -> Engine<Stdout, Stderr> {
+        self.docs = Docs::new(source);
+        self
+    }
     pub fn into_writers(self) -> (Stdout, Stderr) {
-        (self.stdout, self.stderr)
+        (self.stdout.into_inner(), self.stderr)
     }
     pub fn print_preamble(&mut self) {
         writeln!(
@@ -101,9 +211,109 @@ impl<'a, 'b: 'a, 'c, Stdout: Write, Stderr: Write> Engine<Stdout, Stderr> {
         writeln!(self.stdout);
     }
     pub fn print_postamble(&mut self) {
-        #[cfg(feature = "vector_groups")]
         if self.postamble_options.print_flatten {
-            unimplemented!();
+            writeln!(
+                self.stdout,
+                "/** Arrays made of repetitions of the elements of a vector. */"
+            );
+            writeln!(
+                self.stdout,
+                "function flatten(vector: z.ZodType, length: number) {{"
+            );
+            writeln!(
+                self.stdout,
+                "return z.array(z.unknown()).refine((value) => \
+                    value.length % length === 0 \
+                    && Array.from({{ length: value.length / length }}, (_, index) => \
+                        value.slice(index * length, (index + 1) * length)) \
+                    .every((elements) => vector.safeParse(elements).success));"
+            );
+            writeln!(self.stdout, "}}");
+        }
+    }
+    fn is_constrained(&self) -> bool {
+        matches!(
+            self.nested_type1.last().map(|context| context.value_mode),
+            Some(ValueMode::Generic)
+        )
+    }
+    /// Integer ranges exceeding the safe integer range may be decoded as
+    /// either `number` or `bigint`.
+    fn visit_unsafe_range(&mut self, t1: &cddl::ast::Type1<'a>) {
+        let Some(cddl::ast::Operator {
+            operator: cddl::ast::RangeCtlOp::RangeOp { is_inclusive, .. },
+            type2,
+            ..
+        }) = &t1.operator
+        else {
+            return;
+        };
+        write!(
+            self.stdout,
+            "z.union([z.number().int(), z.bigint()]).refine((value) => value >= {} && value {} {})",
+            self.range_bound(&t1.type2),
+            if *is_inclusive { "<=" } else { "<" },
+            self.range_bound(type2)
+        );
+    }
+    /// Integer literals become `bigint` literals so they compare exactly. Other
+    /// bounds refer to the value of their schema.
+    fn range_bound(&self, t2: &cddl::ast::Type2<'a>) -> String {
+        match t2 {
+            cddl::ast::Type2::IntValue { value, .. } => format!("{}n", value),
+            cddl::ast::Type2::UintValue { value, .. } => format!("{}n", value),
+            cddl::ast::Type2::Typename { ident, .. } => {
+                format!("{}Schema.value", self.names.rule(ident.ident))
+            }
+            t2 => t2.to_string(),
+        }
+    }
+    /// Compared integers (e.g. `int .ge -10`) may be decoded as either `number`
+    /// or `bigint`, which `z.number()` methods cannot check.
+    fn visit_int_comparison(&mut self, t1: &cddl::ast::Type1<'a>) -> bool {
+        let cddl::ast::Type2::Typename {
+            ident,
+            generic_args: None,
+            ..
+        } = &t1.type2
+        else {
+            return false;
+        };
+        let Some((number, bigint)) = int_schemas(ident.ident) else {
+            return false;
+        };
+        let Some(cddl::ast::Operator {
+            operator: cddl::ast::RangeCtlOp::CtlOp { ctrl, .. },
+            type2,
+            ..
+        }) = &t1.operator
+        else {
+            return false;
+        };
+        let comparison = match ctrl {
+            cddl::token::ControlOperator::LT => "<",
+            cddl::token::ControlOperator::LE => "<=",
+            cddl::token::ControlOperator::GT => ">",
+            cddl::token::ControlOperator::GE => ">=",
+            _ => return false,
+        };
+        write!(
+            self.stdout,
+            "z.union([{}, {}]).refine((value) => value {} {})",
+            number,
+            bigint,
+            comparison,
+            self.range_bound(type2)
+        );
+        true
+    }
+    fn print_brand(&mut self, tr: &cddl::ast::TypeRule<'a>) {
+        if is_primitive_alias(tr) && self.options.brands.contains(tr.name.ident) {
+            write!(
+                self.stdout,
+                ".brand<{}>()",
+                to_string_literal(self.names.rule(tr.name.ident))
+            );
         }
     }
     fn visit_maybe_enum_type(&mut self, t: &'b cddl::ast::Type<'a>) -> bool {
@@ -117,7 +327,7 @@ impl<'a, 'b: 'a, 'c, Stdout: Write, Stderr: Write> Engine<Stdout, Stderr> {
             write!(self.stdout, "z.enum([");
             for type2 in t.type_choices.iter().map(|choice| &choice.type1.type2) {
                 if let cddl::ast::Type2::TextValue { value, .. } = type2 {
-                    write!(self.stdout, "\"{}\",", value);
+                    write!(self.stdout, "{},", text_literal(value));
                 }
             }
             write!(self.stdout, "])");
@@ -126,6 +336,125 @@ impl<'a, 'b: 'a, 'c, Stdout: Write, Stderr: Write> Engine<Stdout, Stderr> {
             false
         }
     }
+    /// Unions of plain maps sharing a literal member with distinct values (e.g.
+    /// `type: "success"` and `type: "error"`) are discriminated unions.
+    fn find_discriminator(&self, t: &cddl::ast::Type<'a>) -> Option<String> {
+        if t.type_choices.len() < 2 {
+            return None;
+        }
+        let variants = t
+            .type_choices
+            .iter()
+            .map(|choice| {
+                if choice.type1.operator.is_some() {
+                    return None;
+                }
+                match &choice.type1.type2 {
+                    cddl::ast::Type2::Typename {
+                        ident,
+                        generic_args: None,
+                        ..
+                    } if ident.socket.is_none()
+                        && !self.generic_params.iter().any(|param| param == ident.ident) =>
+                    {
+                        self.map_literals.get(ident.ident).cloned()
+                    }
+                    cddl::ast::Type2::Map { group, .. } => {
+                        literal_members(group, &|name| self.map_group_literals(name))
+                    }
+                    _ => None,
+                }
+            })
+            .collect::<Option<Vec<_>>>()?;
+        find_discriminator(&variants).map(String::from)
+    }
+    /// Literal members a group adds to the maps it is used in. Catch-all
+    /// groups add none, but keep the maps objects.
+    fn map_group_literals(&self, name: &str) -> Option<LiteralMembers> {
+        self.plain_groups.get(name).cloned().or_else(|| {
+            self.catch_all_groups
+                .contains_key(name)
+                .then(LiteralMembers::new)
+        })
+    }
+    fn is_plain_group(&self, entry: &cddl::ast::TypeGroupnameEntry<'a>) -> bool {
+        entry.occur.is_none()
+            && entry.generic_args.is_none()
+            && entry.name.socket.is_none()
+            && self.plain_groups.contains_key(entry.name.ident)
+    }
+    /// Whether a reference to a group can be spread into the enclosing object.
+    /// A group on its own is referenced as is, unless the object is strict.
+    fn is_spread_group(&self, entry: &cddl::ast::TypeGroupnameEntry<'a>) -> bool {
+        !self
+            .nested_group_choices
+            .last()
+            .is_some_and(|group| group.is_first && group.is_last && !group.is_strict)
+            && self.is_plain_group(entry)
+    }
+    /// Whether the entry is a named member or a plain group, i.e. it becomes
+    /// part of an object.
+    fn is_object_member(&self, entry: &cddl::ast::GroupEntry<'a>) -> bool {
+        match entry {
+            cddl::ast::GroupEntry::ValueMemberKey { ge, .. } => matches!(
+                ge.member_key,
+                Some(cddl::ast::MemberKey::Bareword { .. } | cddl::ast::MemberKey::Value { .. })
+            ),
+            cddl::ast::GroupEntry::TypeGroupname { ge, .. } => self.is_plain_group(ge),
+            cddl::ast::GroupEntry::InlineGroup { .. } => false,
+        }
+    }
+    fn index_catch_all_groups(
+        &mut self,
+        cddl: &'b cddl::ast::CDDL<'a>,
+    ) -> cddl::visitor::Result<Error> {
+        for rule in &cddl.rules {
+            let cddl::ast::Rule::Group { rule, .. } = rule else {
+                continue;
+            };
+            if let Some(t) = catch_all_group_type(rule) {
+                let schema = self.render_type(t)?;
+                self.catch_all_groups
+                    .insert(rule.name.ident.to_string(), schema);
+            }
+        }
+        Ok(())
+    }
+    /// Writes the schema of a type to a string rather than the output.
+    fn render_type(&mut self, t: &'b cddl::ast::Type<'a>) -> Result<String, Error> {
+        let mut engine =
+            Engine::with_writers(Vec::new(), Vec::new()).with_options(self.options.clone());
+        engine.generic_params = self.generic_params.clone();
+        engine.plain_groups = self.plain_groups.clone();
+        engine.map_literals = self.map_literals.clone();
+        engine.catch_all_groups = self.catch_all_groups.clone();
+        engine.rule = self.rule.clone();
+        engine.names = self.names.clone();
+        engine.array_groups = self.array_groups.clone();
+        engine.array_lengths = self.array_lengths.clone();
+        engine.visit_type(t)?;
+        self.sockets.append(&mut engine.sockets);
+        self.postamble_options.print_flatten |= engine.postamble_options.print_flatten;
+        Ok(String::from_utf8(engine.stdout.take_unformatted()).unwrap())
+    }
+    /// The value schema if the entry is a catch-all member or a catch-all
+    /// group.
+    fn visit_catch_all(
+        &mut self,
+        entry: &'b cddl::ast::GroupEntry<'a>,
+    ) -> Result<Option<String>, Error> {
+        if let Some(t) = catch_all_type(entry) {
+            return self.render_type(t).map(Some);
+        }
+        match entry {
+            cddl::ast::GroupEntry::TypeGroupname { ge, .. }
+                if ge.occur.is_none() && ge.generic_args.is_none() && ge.name.socket.is_none() =>
+            {
+                Ok(self.catch_all_groups.get(ge.name.ident).cloned())
+            }
+            _ => Ok(None),
+        }
+    }
     fn visit_array(&mut self, g: &'b cddl::ast::Group<'a>) -> cddl::visitor::Result<Error> {
         if g.group_choices.len() != 1 {
             write!(self.stdout, "z.union([");
@@ -148,8 +477,12 @@ impl<'a, 'b: 'a, 'c, Stdout: Write, Stderr: Write> Engine<Stdout, Stderr> {
         self.nested_group_choices.push(GroupChoiceContext {
             in_object: false,
             is_first: true,
+            is_last: true,
             in_record: false,
             in_and: false,
+            catch_all: None,
+            is_strict: false,
+            renamed: Vec::new(),
         });
         if gc.group_entries.is_empty() {
             self.enter_tuple();
@@ -221,7 +554,12 @@ impl<'a, 'b: 'a, 'c, Stdout: Write, Stderr: Write> Engine<Stdout, Stderr> {
     fn enter_map(&mut self) {
         if let Some(group) = self.nested_group_choices.last_mut() {
             if !group.in_object {
-                writeln!(self.stdout, "z.object({{");
+                let constructor = match &group.catch_all {
+                    Some(schema) if is_any_schema(schema) => "z.looseObject",
+                    _ if group.is_strict => "z.strictObject",
+                    _ => "z.object",
+                };
+                writeln!(self.stdout, "{}({{", constructor);
                 group.in_object = true;
             }
         }
@@ -231,6 +569,26 @@ impl<'a, 'b: 'a, 'c, Stdout: Write, Stderr: Write> Engine<Stdout, Stderr> {
             if group.in_object {
                 group.in_object = false;
                 write!(self.stdout, "}})");
+                match &group.catch_all {
+                    Some(schema) if !is_any_schema(schema) => {
+                        write!(self.stdout, ".catchall({})", schema);
+                    }
+                    _ => {}
+                }
+                let renamed = std::mem::take(&mut group.renamed);
+                if !renamed.is_empty() {
+                    let (mut from, mut to) = (Vec::new(), Vec::new());
+                    for (index, (key, name)) in renamed.iter().enumerate() {
+                        from.push(format!("{}: value{}", to_string_literal(key), index));
+                        to.push(format!("{}: value{}", to_string_literal(name), index));
+                    }
+                    write!(
+                        self.stdout,
+                        ".transform(({{ {}, ...rest }}) => ({{ ...rest, {} }}))",
+                        from.join(", "),
+                        to.join(", ")
+                    );
+                }
             }
         }
         self.exit_and();
@@ -268,6 +626,12 @@ impl<'a, 'b: 'a, 'c, Stdout: Write, Stderr: Write> Engine<Stdout, Stderr> {
                     ));
                 }
                 let (lower, upper) = calculate_occurrence(&ge.occur);
+                if self.is_vector(&ge.name) {
+                    let length = self.array_lengths[ge.name.ident];
+                    return self.visit_vectors(length, (lower, upper), |engine| {
+                        engine.visit_array_group(ge)
+                    });
+                }
                 if lower != upper || lower != 1 {
                     return Err(Error::CDDL(
                         "Multiplicity for array types is not supported.".to_string(),
@@ -282,7 +646,12 @@ impl<'a, 'b: 'a, 'c, Stdout: Write, Stderr: Write> Engine<Stdout, Stderr> {
                             .to_string(),
                     ));
                 }
-                let (lower, upper) = calculate_occurrence(&occur);
+                let (lower, upper) = calculate_occurrence(occur);
+                if self.options.vector_groups {
+                    let length = group_length(group, &self.array_lengths);
+                    return self
+                        .visit_vectors(length, (lower, upper), |engine| engine.visit_array(group));
+                }
                 if lower != upper || lower != 1 {
                     return Err(Error::CDDL(
                         "Multiplicity for array types is not supported.".to_string(),
@@ -353,28 +722,354 @@ impl<'a, 'b: 'a, 'c, Stdout: Write, Stderr: Write> Engine<Stdout, Stderr> {
         self.visit_identifier_with_args(&entry.name, &entry.generic_args)
     }
 
+    /// The schema of a byte string value in `mode`, which compares its
+    /// bytes: their base64url in JSON, or a `Uint8Array` of them in CBOR.
+    fn byte_value(&mut self, value: &cddl::token::ByteValue, mode: ValueMode) -> String {
+        let any_bytes = match self.options.profile {
+            Profile::Json => "z.string()",
+            Profile::Cbor => "z.instanceof(Uint8Array)",
+        };
+        let bytes = match byte_value(value) {
+            Some(bytes) => bytes,
+            None => {
+                writeln!(
+                    self.stderr,
+                    "In `{}`, `{}` is not a valid byte string; it is any byte string.",
+                    self.rule, value
+                );
+                return match mode {
+                    ValueMode::JavaScript => to_string_literal(value.to_string()),
+                    _ => any_bytes.to_string(),
+                };
+            }
+        };
+        match (mode, self.options.profile) {
+            (ValueMode::Generic, _) => any_bytes.to_string(),
+            (ValueMode::Literal, Profile::Json) => {
+                format!("z.literal({})", to_string_literal(to_base64url(&bytes)))
+            }
+            (ValueMode::Literal, Profile::Cbor) => format!(
+                "{}.refine((value) => value.length === {} && {:?}.every((byte, index) => value[index] === byte))",
+                any_bytes,
+                bytes.len(),
+                bytes
+            ),
+            (ValueMode::JavaScript, Profile::Json) => to_string_literal(to_base64url(&bytes)),
+            (ValueMode::JavaScript, Profile::Cbor) => format!("new Uint8Array({:?})", bytes),
+        }
+    }
+
+    /// Whether a group used in an array is emitted as its `Vector` schema.
+    fn is_vector(&self, name: &cddl::ast::Identifier<'a>) -> bool {
+        self.options.vector_groups && self.array_groups.contains(name.ident)
+    }
+
+    fn visit_array_group(
+        &mut self,
+        entry: &'b cddl::ast::TypeGroupnameEntry<'a>,
+    ) -> cddl::visitor::Result<Error> {
+        write!(
+            self.stdout,
+            "{}VectorSchema",
+            self.names.rule(entry.name.ident)
+        );
+        self.print_generic_args(&entry.generic_args)
+    }
+
+    /// Writes the schema of an array made of `lower` to `upper` repetitions of
+    /// a vector of `length` elements, whose schema `visit` writes.
+    fn visit_vectors(
+        &mut self,
+        length: Option<usize>,
+        (lower, upper): (usize, usize),
+        visit: impl FnOnce(&mut Self) -> cddl::visitor::Result<Error>,
+    ) -> cddl::visitor::Result<Error> {
+        if lower == 1 && upper == 1 {
+            return visit(self);
+        }
+        let Some(length) = length else {
+            return Err(Error::CDDL(
+                "Repeated groups in arrays must have a fixed number of elements.".to_string(),
+            ));
+        };
+        self.postamble_options.print_flatten = true;
+        write!(self.stdout, "flatten(");
+        visit(self)?;
+        write!(self.stdout, ", {})", length);
+        if lower > 0 {
+            write!(self.stdout, ".min({})", lower * length);
+        }
+        if upper < usize::MAX {
+            write!(self.stdout, ".max({})", upper * length);
+        }
+        Ok(())
+    }
+
     fn visit_identifier_with_params(
         &mut self,
         ident: &cddl::ast::Identifier<'a>,
         _params: &Option<cddl::ast::GenericParams<'a>>,
     ) -> cddl::visitor::Result<Error> {
-        self.visit_identifier(ident)
+        write!(self.stdout, "{}Schema", ident.ident);
+        Ok(())
+    }
+
+    /// Generic rules are emitted as factory functions taking the schemas of
+    /// their parameters, e.g. `<T extends z.ZodType>(t: T) => ...`.
+    /// Writes `.describe(...)` with the documentation of a schema, if any.
+    fn print_description(&mut self, doc: Option<String>) {
+        if let Some(doc) = doc {
+            write!(self.stdout, ".describe({})", to_string_literal(doc));
+        }
+    }
+    fn enter_generic_params(&mut self, params: &Option<cddl::ast::GenericParams<'a>>) {
+        let Some(params) = params else {
+            return;
+        };
+        write!(self.stdout, "<");
+        for param in &params.params {
+            write!(
+                self.stdout,
+                "{} extends z.ZodType,",
+                self.names.identifier(&to_pascalcase(param.param.ident))
+            );
+        }
+        write!(self.stdout, ">(");
+        for param in &params.params {
+            write!(
+                self.stdout,
+                "{}: {},",
+                self.names.identifier(&to_camelcase(param.param.ident)),
+                self.names.identifier(&to_pascalcase(param.param.ident))
+            );
+        }
+        write!(self.stdout, ") => ");
+        self.generic_params = params
+            .params
+            .iter()
+            .map(|param| param.param.ident.to_string())
+            .collect();
+    }
+
+    fn exit_generic_params(&mut self) {
+        self.generic_params.clear();
     }
 
     fn visit_identifier_with_args(
         &mut self,
         ident: &cddl::ast::Identifier<'a>,
-        _params: &Option<cddl::ast::GenericArgs<'a>>,
+        args: &Option<cddl::ast::GenericArgs<'a>>,
     ) -> cddl::visitor::Result<Error> {
-        self.visit_identifier(ident)
+        self.visit_identifier(ident)?;
+        self.print_generic_args(args)
+    }
+
+    fn print_generic_args(
+        &mut self,
+        args: &Option<cddl::ast::GenericArgs<'a>>,
+    ) -> cddl::visitor::Result<Error> {
+        if let Some(args) = args {
+            write!(self.stdout, "(");
+            for arg in &args.args {
+                self.visit_type1(&arg.arg)?;
+                write!(self.stdout, ",");
+            }
+            write!(self.stdout, ")");
+        }
+        Ok(())
+    }
+
+    fn define_socket(&mut self, name: &cddl::ast::Identifier<'a>) {
+        if let Some(socket) = name.socket {
+            self.sockets.insert(name.ident.to_string(), socket);
+            self.defined_sockets.insert(name.ident.to_string());
+        }
+    }
+
+    /// Sockets are backed by mutable registries so that they can be extended
+    /// before the (lazy) schemas are first used. Type sockets accept any of
+    /// the registered schemas; group sockets are loose objects with the
+    /// registered shape.
+    fn print_sockets(&mut self) {
+        for (name, socket) in std::mem::take(&mut self.sockets) {
+            let (namespaces, type_name) = self.names.split_rule(&name);
+            for namespace in &namespaces {
+                writeln!(self.stdout, "export namespace {} {{", namespace);
+            }
+            match socket {
+                SocketPlug::TYPE => {
+                    writeln!(self.stdout, "/** Push schemas to extend `${}`. */", name);
+                    writeln!(
+                        self.stdout,
+                        "export const {}Socket: z.ZodType[] = [];",
+                        type_name
+                    );
+                    if !self.defined_sockets.contains(&name) {
+                        writeln!(
+                            self.stdout,
+                            "export const {}Schema = z.lazy(() => z.union([z.never(), ...{}Socket]));",
+                            type_name, type_name
+                        );
+                    }
+                }
+                SocketPlug::GROUP => {
+                    writeln!(self.stdout, "/** Add properties to extend `$${}`. */", name);
+                    writeln!(
+                        self.stdout,
+                        "export const {}Socket: Record<string, z.ZodType> = {{}};",
+                        type_name
+                    );
+                    if !self.defined_sockets.contains(&name) {
+                        writeln!(
+                            self.stdout,
+                            "export const {}Schema = z.lazy(() => z.looseObject({}Socket));",
+                            type_name, type_name
+                        );
+                    }
+                }
+            }
+            for _ in &namespaces {
+                writeln!(self.stdout, "}}");
+            }
+        }
+    }
+
+    fn visit_prelude(&mut self, ident: &'static str) -> cddl::visitor::Result<Error> {
+        self.visit_identifier(&cddl::ast::Identifier {
+            ident,
+            socket: None,
+            span: Default::default(),
+        })
+    }
+
+    /// Well-known tags are mapped to their natural representation, or to their
+    /// declared content type if it differs from the tag's own. Other tags are
+    /// transparent unless `cbor_tags` is set.
+    fn visit_tagged_data(
+        &mut self,
+        tag: &Option<cddl::token::TagConstraint<'a>>,
+        t: Option<&'b cddl::ast::Type<'a>>,
+    ) -> cddl::visitor::Result<Error> {
+        let tag = tag.as_ref().and_then(|tag| tag.as_literal());
+        match well_known_tag(tag, t) {
+            Some(WellKnownTag::Prelude(prelude)) => return self.visit_prelude(prelude),
+            Some(WellKnownTag::Content(t)) => return self.visit_type(t),
+            None => {}
+        }
+        if self.options.cbor_tags {
+            match tag {
+                Some(tag) => write!(self.stdout, "z.object({{tag: z.literal({}), value: ", tag),
+                None => write!(
+                    self.stdout,
+                    "z.object({{tag: z.number().int().nonnegative(), value: "
+                ),
+            };
+        }
+        match t {
+            Some(t) => self.visit_type(t)?,
+            None => {
+                write!(self.stdout, "z.unknown()");
+            }
+        }
+        if self.options.cbor_tags {
+            write!(self.stdout, "}})");
+        }
+        Ok(())
+    }
+
+    fn visit_major_type(
+        &mut self,
+        mt: u8,
+        constraint: &Option<cddl::token::TagConstraint<'a>>,
+    ) -> cddl::visitor::Result<Error> {
+        let constraint_value = constraint
+            .as_ref()
+            .and_then(|constraint| constraint.as_literal());
+        if let Some(prelude) = major_type_prelude(mt, constraint_value) {
+            return self.visit_prelude(prelude);
+        }
+        match mt {
+            4 => {
+                write!(self.stdout, "z.array(z.unknown())");
+            }
+            5 => {
+                write!(self.stdout, "z.record(z.string(), z.unknown())");
+            }
+            6 => self.visit_tagged_data(constraint, None)?,
+            7 => match constraint_value {
+                Some(_) => {
+                    write!(self.stdout, "z.unknown()");
+                }
+                None => {
+                    write!(
+                        self.stdout,
+                        "z.union([z.boolean(), z.null(), z.undefined(), z.number()])"
+                    );
+                }
+            },
+            _ => {
+                writeln!(self.stderr, "Unknown major type: {}", mt);
+                write!(self.stdout, "z.unknown()");
+            }
+        }
+        Ok(())
     }
 }
 
 impl<'a, 'b: 'a, Stdout: Write, Stderr: Write> Visitor<'a, 'b, Error> for Engine<Stdout, Stderr> {
+    fn visit_cddl(&mut self, cddl: &'b cddl::ast::CDDL<'a>) -> cddl::visitor::Result<Error> {
+        let cddl = &merge_amendments(cddl);
+        self.enums = choice_enums(cddl);
+        let (cddl, unwraps) = unwrap(cddl);
+        let cddl = &cddl;
+        for unwrap in unwraps {
+            writeln!(self.stderr, "{}", unwrap);
+        }
+        if self.options.vector_groups {
+            self.array_groups = array_groups(cddl);
+            self.array_lengths = array_lengths(cddl, &self.array_groups);
+        }
+        let sockets = sockets(cddl);
+        let (names, collisions) = Names::new(cddl, &self.options.renames, RESERVED, |ident| {
+            let mut affixes = Vec::new();
+            if self.array_groups.contains(ident) {
+                affixes.push(("", "Vector"));
+            }
+            if sockets.contains(ident) {
+                affixes.push(("", "Socket"));
+            }
+            affixes
+        });
+        self.names = names;
+        for collision in collisions {
+            writeln!(self.stderr, "{}", collision);
+        }
+        self.index_catch_all_groups(cddl)?;
+        self.plain_groups = plain_groups(cddl, &self.options.renames);
+        self.map_literals = map_literals(cddl, &self.options.renames, &|name| {
+            self.map_group_literals(name)
+        });
+        cddl::visitor::walk_cddl(self, cddl)?;
+        self.print_sockets();
+        Ok(())
+    }
     fn visit_identifier(
         &mut self,
         ident: &cddl::ast::Identifier<'a>,
     ) -> cddl::visitor::Result<Error> {
+        if let Some(socket) = ident.socket {
+            self.sockets.insert(ident.ident.to_string(), socket);
+            write!(self.stdout, "{}Schema", self.names.rule(ident.ident));
+            return Ok(());
+        }
+        if self.generic_params.iter().any(|param| param == ident.ident) {
+            write!(
+                self.stdout,
+                "{}",
+                self.names.identifier(&to_camelcase(ident.ident))
+            );
+            return Ok(());
+        }
         if matches!(
             self.nested_type1
                 .last()
@@ -404,29 +1099,36 @@ impl<'a, 'b: 'a, Stdout: Write, Stderr: Write> Visitor<'a, 'b, Error> for Engine
         }
         match ident.ident {
             "bool" => write!(self.stdout, "z.boolean()"),
-            "uint" => {
-                write!(self.stdout, "z.number().int().nonnegative()")
-            }
-            "nint" => {
-                write!(self.stdout, "z.number().int().negative()")
-            }
-            "int" => {
-                write!(self.stdout, "z.number().int()")
+            "uint" | "nint" | "int" => {
+                let (number, bigint) = int_schemas(ident.ident).unwrap();
+                // Constrained integers (e.g. `uint .le 10`) need the methods of
+                // `z.number()`, so they never become unions.
+                if self.options.profile == Profile::Cbor && !self.is_constrained() {
+                    write!(self.stdout, "z.union([{}, {}])", number, bigint)
+                } else {
+                    write!(self.stdout, "{}", number)
+                }
             }
             "float16" | "float32" | "float64" | "float16-32" | "float32-64" | "float"
             | "number" => {
                 write!(self.stdout, "z.number()")
             }
-            "biguint" => {
-                write!(self.stdout, "z.bigint().nonnegative()")
-            }
-            "bignint" => {
-                write!(self.stdout, "z.bigint().negative()")
-            }
-            "bigint" => {
-                write!(self.stdout, "z.bigint()")
-            }
-            "bstr" | "bytes" => write!(self.stdout, "z.string()"),
+            "biguint" => match self.options.profile {
+                Profile::Json => write!(self.stdout, "z.number().int().nonnegative()"),
+                Profile::Cbor => write!(self.stdout, "z.bigint().nonnegative()"),
+            },
+            "bignint" => match self.options.profile {
+                Profile::Json => write!(self.stdout, "z.number().int().negative()"),
+                Profile::Cbor => write!(self.stdout, "z.bigint().negative()"),
+            },
+            "bigint" => match self.options.profile {
+                Profile::Json => write!(self.stdout, "z.number().int()"),
+                Profile::Cbor => write!(self.stdout, "z.bigint()"),
+            },
+            "bstr" | "bytes" | "encoded-cbor" => match self.options.profile {
+                Profile::Json => write!(self.stdout, "z.string()"),
+                Profile::Cbor => write!(self.stdout, "z.instanceof(Uint8Array)"),
+            },
             "tstr" | "text" => write!(self.stdout, "z.string()"),
             "any" => write!(self.stdout, "z.any()"),
             "nil" | "null" => write!(self.stdout, "z.null()"),
@@ -435,12 +1137,20 @@ impl<'a, 'b: 'a, Stdout: Write, Stderr: Write> Visitor<'a, 'b, Error> for Engine
             "undefined" => write!(self.stdout, "z.undefined()"),
             "uri" => write!(self.stdout, "z.url()"),
             "regexp" => write!(self.stdout, "z.string()"),
-            ident => write!(self.stdout, "{}Schema", to_namespaced(ident)),
+            // RFC 3339 date-times may have any offset, not only `Z`.
+            "tdate" => write!(self.stdout, "z.iso.datetime({{ offset: true }})"),
+            "time" => write!(self.stdout, "z.number()"),
+            "b64url" | "b64legacy" | "mime-message" => write!(self.stdout, "z.string()"),
+            "cbor-any" => write!(self.stdout, "z.any()"),
+            ident => write!(self.stdout, "{}Schema", self.names.rule(ident)),
         };
         Ok(())
     }
     fn visit_type_rule(&mut self, tr: &'b cddl::ast::TypeRule<'a>) -> cddl::visitor::Result<Error> {
-        let (namespaces, type_name) = split_namespaced(&tr.name);
+        self.define_socket(&tr.name);
+        self.docs.enter_rule(&tr.name);
+        self.rule = tr.name.ident.to_string();
+        let (namespaces, type_name) = self.names.split_rule(tr.name.ident);
         for namespace in &namespaces {
             writeln!(self.stdout, "export namespace {} {{", namespace);
         }
@@ -454,15 +1164,36 @@ impl<'a, 'b: 'a, Stdout: Write, Stderr: Write> Visitor<'a, 'b, Error> for Engine
             &tr.generic_params,
         )?;
         write!(self.stdout, " = ");
-        if tr.value.type_choices.len() == 1
+        self.enter_generic_params(&tr.generic_params);
+        if let Some(members) = self.enums.get(tr.name.ident).cloned() {
+            write!(self.stdout, "z.enum({{");
+            let (names, collisions) = self
+                .names
+                .members(tr.name.ident, members.iter().map(|(name, _)| name.as_str()));
+            for collision in collisions {
+                writeln!(self.stderr, "{}", collision);
+            }
+            for (name, (_, value)) in names.into_iter().zip(members) {
+                write!(self.stdout, "{}:{},", to_string_literal(name), value);
+            }
+            write!(self.stdout, "}})");
+        } else if tr.name.socket.is_some() {
+            write!(self.stdout, "z.lazy(() => z.union([");
+            self.visit_type(&tr.value)?;
+            write!(self.stdout, ", ...{}Socket]))", type_name);
+        } else if tr.value.type_choices.len() == 1
             && is_primitive_type(&tr.value.type_choices.first().unwrap().type1.type2)
         {
             self.visit_type(&tr.value)?;
+            self.print_brand(tr);
         } else {
             write!(self.stdout, "z.lazy(() => ");
             self.visit_type(&tr.value)?;
+            self.print_brand(tr);
             write!(self.stdout, ")");
         }
+        self.print_description(self.docs.type_rule(tr));
+        self.exit_generic_params();
         writeln!(self.stdout, ";");
         for _ in &namespaces {
             writeln!(self.stdout, "}}");
@@ -473,7 +1204,13 @@ impl<'a, 'b: 'a, Stdout: Write, Stderr: Write> Visitor<'a, 'b, Error> for Engine
         if self.visit_maybe_enum_type(&t) {
             return Ok(());
         }
-        if t.type_choices.len() != 1 {
+        if let Some(discriminator) = self.find_discriminator(t) {
+            write!(
+                self.stdout,
+                "z.discriminatedUnion({}, [",
+                to_string_literal(discriminator)
+            );
+        } else if t.type_choices.len() != 1 {
             write!(self.stdout, "z.union([");
         }
         for i in 0..t.type_choices.len() {
@@ -491,7 +1228,10 @@ impl<'a, 'b: 'a, Stdout: Write, Stderr: Write> Visitor<'a, 'b, Error> for Engine
         &mut self,
         gr: &'b cddl::ast::GroupRule<'a>,
     ) -> cddl::visitor::Result<Error> {
-        let (namespaces, type_name) = split_namespaced(&gr.name);
+        self.define_socket(&gr.name);
+        self.docs.enter_rule(&gr.name);
+        self.rule = gr.name.ident.to_string();
+        let (namespaces, type_name) = self.names.split_rule(gr.name.ident);
         for namespace in &namespaces {
             writeln!(self.stdout, "export namespace {} {{", namespace);
         }
@@ -524,12 +1264,35 @@ impl<'a, 'b: 'a, Stdout: Write, Stderr: Write> Visitor<'a, 'b, Error> for Engine
             },
             &gr.generic_params,
         )?;
-        write!(self.stdout, " = z.lazy(() => ");
+        write!(self.stdout, " = ");
+        self.enter_generic_params(&gr.generic_params);
+        write!(self.stdout, "z.lazy(() => ");
         self.visit_group_choice(&choice)?;
-        writeln!(self.stdout, ");");
+        if gr.name.socket.is_some() {
+            write!(self.stdout, ".and(z.looseObject({}Socket))", type_name);
+        }
+        write!(self.stdout, ")");
+        self.print_description(self.docs.group_rule(gr));
+        self.exit_generic_params();
+        writeln!(self.stdout, ";");
 
-        if cfg!(feature = "vector_groups") {
-            unimplemented!();
+        if self.is_vector(&gr.name) {
+            write!(self.stdout, "export const ");
+            self.visit_identifier_with_params(
+                &cddl::ast::Identifier {
+                    ident: &format!("{}Vector", type_name),
+                    socket: None,
+                    span: Default::default(),
+                },
+                &gr.generic_params,
+            )?;
+            write!(self.stdout, " = ");
+            self.enter_generic_params(&gr.generic_params);
+            write!(self.stdout, "z.lazy(() => ");
+            self.visit_array_choice(&choice)?;
+            write!(self.stdout, ")");
+            self.exit_generic_params();
+            writeln!(self.stdout, ";");
         }
 
         for _ in &namespaces {
@@ -545,6 +1308,15 @@ impl<'a, 'b: 'a, Stdout: Write, Stderr: Write> Visitor<'a, 'b, Error> for Engine
             cddl::ast::GroupEntry::ValueMemberKey { ge, .. } => {
                 self.visit_value_member_key_entry(ge)?;
             }
+            cddl::ast::GroupEntry::TypeGroupname { ge, .. } if self.is_spread_group(ge) => {
+                // Plain groups are spread into the enclosing object rather
+                // than intersected with it.
+                self.print_group_joiner();
+                self.enter_map();
+                write!(self.stdout, "...");
+                self.visit_identifier(&ge.name)?;
+                write!(self.stdout, ".unwrap().shape");
+            }
             cddl::ast::GroupEntry::TypeGroupname { ge, .. } => {
                 self.exit_map();
                 self.print_group_joiner();
@@ -572,16 +1344,19 @@ impl<'a, 'b: 'a, Stdout: Write, Stderr: Write> Visitor<'a, 'b, Error> for Engine
         &mut self,
         entry: &'b cddl::ast::ValueMemberKeyEntry<'a>,
     ) -> cddl::visitor::Result<Error> {
-        let mk = entry.member_key.as_ref().expect(&format!(
-            "Expected member key for type {} since the current ambient rule is a map. \
-            Did you mean to declare {} with parenthesis (`( .. )`) \
-            instead of brackets (`{{ .. }}`)?",
-            entry.entry_type, entry.entry_type
-        ));
-        self.visit_memberkey(&mk)?;
+        let Some(mk) = entry.member_key.as_ref() else {
+            return Err(Error::CDDL(format!(
+                "Expected member key for type {} since the current ambient rule is a map. \
+                Did you mean to declare {} with parenthesis (`( .. )`) \
+                instead of brackets (`{{ .. }}`)?",
+                entry.entry_type, entry.entry_type
+            )));
+        };
+        self.visit_memberkey(mk)?;
         self.visit_type(&entry.entry_type)?;
+        self.print_description(self.docs.member(entry));
         if matches!(calculate_occurrence(&entry.occur), (0, max) if max > 0)
-            && !matches!(&mk, cddl::ast::MemberKey::Type1 { is_cut: false, .. })
+            && !matches!(mk, cddl::ast::MemberKey::Type1 { is_cut: false, .. })
         {
             write!(self.stdout, ".optional()");
         }
@@ -596,6 +1371,7 @@ impl<'a, 'b: 'a, Stdout: Write, Stderr: Write> Visitor<'a, 'b, Error> for Engine
         Ok(())
     }
     fn visit_group(&mut self, g: &'b cddl::ast::Group<'a>) -> cddl::visitor::Result<Error> {
+        let in_map = std::mem::take(&mut self.in_map);
         if g.group_choices.len() != 1 {
             write!(self.stdout, "z.union([");
         }
@@ -603,6 +1379,7 @@ impl<'a, 'b: 'a, Stdout: Write, Stderr: Write> Visitor<'a, 'b, Error> for Engine
             if i != 0 {
                 write!(self.stdout, ",");
             }
+            self.in_map = in_map;
             self.visit_group_choice(&g.group_choices[i])?;
         }
         if g.group_choices.len() != 1 {
@@ -614,17 +1391,49 @@ impl<'a, 'b: 'a, Stdout: Write, Stderr: Write> Visitor<'a, 'b, Error> for Engine
         &mut self,
         gc: &'b cddl::ast::GroupChoice<'a>,
     ) -> cddl::visitor::Result<Error> {
+        let in_map = std::mem::take(&mut self.in_map);
+        // Catch-all members alongside named members become the catch-all of
+        // the object rather than an intersection with a record.
+        let mut entries = Vec::new();
+        let mut catch_alls = Vec::new();
+        for (entry, _) in &gc.group_entries {
+            match self.visit_catch_all(entry)? {
+                Some(schema) => catch_alls.push(schema),
+                None => entries.push(entry),
+            }
+        }
+        if !entries.iter().any(|entry| self.is_object_member(entry)) {
+            entries = gc.group_entries.iter().map(|(entry, _)| entry).collect();
+            catch_alls.clear();
+        }
+        let is_strict = in_map
+            && self.options.extensibility == Extensibility::Strict
+            && catch_alls.is_empty()
+            && entries.iter().all(|entry| self.is_object_member(entry));
+        let mut seen = BTreeSet::new();
+        catch_alls.retain(|schema| seen.insert(schema.clone()));
+        let catch_all = match (self.options.extensibility, catch_alls.as_slice()) {
+            (Extensibility::Closed, _) | (_, []) => None,
+            (_, [schema]) => Some(schema.clone()),
+            (_, schemas) => Some(format!("z.union([{}])", schemas.join(","))),
+        };
         self.nested_group_choices.push(GroupChoiceContext {
             in_object: false,
             is_first: true,
+            is_last: true,
             in_record: false,
             in_and: false,
+            catch_all,
+            is_strict,
+            renamed: Vec::new(),
         });
-        if gc.group_entries.is_empty() {
+        if entries.is_empty() {
             self.enter_map();
         }
-        for (index, (entry, _)) in gc.group_entries.iter().enumerate() {
-            self.nested_group_choices.last_mut().unwrap().is_first = index == 0;
+        for (index, entry) in entries.iter().enumerate() {
+            let group = self.nested_group_choices.last_mut().unwrap();
+            group.is_first = index == 0;
+            group.is_last = index == entries.len() - 1;
             self.visit_group_entry(entry)?;
         }
         self.exit_map();
@@ -643,21 +1452,16 @@ impl<'a, 'b: 'a, Stdout: Write, Stderr: Write> Visitor<'a, 'b, Error> for Engine
                 self.visit_type1(t1)?;
                 write!(self.stdout, ",");
             }
-            cddl::ast::MemberKey::Bareword { ident, .. } => {
-                self.print_group_joiner();
-                self.enter_map();
-                write!(self.stdout, "\"{}\":", &ident);
-            }
-            cddl::ast::MemberKey::Value { value, .. } => {
+            cddl::ast::MemberKey::Bareword { .. } | cddl::ast::MemberKey::Value { .. } => {
                 self.print_group_joiner();
                 self.enter_map();
-                match value {
-                    cddl::token::Value::INT(value) => write!(self.stdout, "\"{}\":", value),
-                    cddl::token::Value::UINT(value) => write!(self.stdout, "\"{}\":", value),
-                    cddl::token::Value::FLOAT(value) => write!(self.stdout, "\"{}\":", value),
-                    cddl::token::Value::TEXT(value) => write!(self.stdout, "\"{}\":", value),
-                    cddl::token::Value::BYTE(value) => write!(self.stdout, "\"{}\":", value),
-                };
+                let key = member_key_name(mk).unwrap();
+                if let Some(name) = self.options.renames.property(&self.rule, &key) {
+                    if let Some(group) = self.nested_group_choices.last_mut() {
+                        group.renamed.push((key.clone(), name.to_string()));
+                    }
+                }
+                write!(self.stdout, "{}:", to_string_literal(key));
             }
             cddl::ast::MemberKey::NonMemberKey { .. } => {
                 unimplemented!()
@@ -669,6 +1473,15 @@ impl<'a, 'b: 'a, Stdout: Write, Stderr: Write> Visitor<'a, 'b, Error> for Engine
         self.nested_type1.push(Type1Context {
             value_mode: ValueMode::Generic,
         });
+        if self.options.profile == Profile::Cbor && !is_safe_range(t1) {
+            self.visit_unsafe_range(t1);
+            self.nested_type1.pop();
+            return Ok(());
+        }
+        if self.options.profile == Profile::Cbor && self.visit_int_comparison(t1) {
+            self.nested_type1.pop();
+            return Ok(());
+        }
         if let Some(op) = &t1.operator {
             self.visit_type2(&t1.type2)?;
 
@@ -695,12 +1508,46 @@ impl<'a, 'b: 'a, Stdout: Write, Stderr: Write> Visitor<'a, 'b, Error> for Engine
                         self.visit_type2(&op.type2)?;
                         write!(self.stdout, ")");
                     }
+                    cddl::token::ControlOperator::SIZE
+                        if self.options.profile == Profile::Cbor
+                            && matches!(
+                                &t1.type2,
+                                cddl::ast::Type2::Typename { ident, .. }
+                                    if matches!(ident.ident, "bstr" | "bytes")
+                            ) =>
+                    {
+                        // `z.instanceof` has no `.length`.
+                        write!(self.stdout, ".refine((value) => value.length === ");
+                        self.visit_type2(&op.type2)?;
+                        write!(self.stdout, ")");
+                    }
                     cddl::token::ControlOperator::SIZE => {
                         write!(self.stdout, ".length(");
                         self.visit_type2(&op.type2)?;
                         write!(self.stdout, ")");
                     }
-                    cddl::token::ControlOperator::PCRE | cddl::token::ControlOperator::REGEXP => {
+                    cddl::token::ControlOperator::REGEXP => match &op.type2 {
+                        cddl::ast::Type2::TextValue { value, .. } => {
+                            match regexp::to_ecmascript(value) {
+                                Ok(source) => write!(
+                                    self.stdout,
+                                    ".regex(new RegExp({}))",
+                                    to_string_literal(&source)
+                                ),
+                                Err(reason) => writeln!(
+                                    self.stderr,
+                                    "In `{}`, the pattern `{}` is not checked, as {}.",
+                                    self.rule, value, reason
+                                ),
+                            };
+                        }
+                        _ => {
+                            write!(self.stdout, ".regex(new RegExp(");
+                            self.visit_type2(&op.type2)?;
+                            write!(self.stdout, "))");
+                        }
+                    },
+                    cddl::token::ControlOperator::PCRE => {
                         write!(self.stdout, ".regex(new RegExp(");
                         self.visit_type2(&op.type2)?;
                         write!(self.stdout, "))");
@@ -755,9 +1602,34 @@ impl<'a, 'b: 'a, Stdout: Write, Stderr: Write> Visitor<'a, 'b, Error> for Engine
             cddl::ast::Type2::Array { group, .. } => {
                 self.visit_array(&group)?;
             }
+            cddl::ast::Type2::Map { group, .. } => {
+                // Maps combined with others by a control operator (e.g. `.and`)
+                // cannot reject their members.
+                self.in_map = matches!(
+                    self.nested_type1.last().map(|context| context.value_mode),
+                    Some(ValueMode::Literal)
+                );
+                self.visit_group(group)?;
+            }
             cddl::ast::Type2::Any { .. } => {
                 write!(self.stdout, "z.unknown()");
             }
+            // Choices from groups are resolved beforehand when they can be.
+            cddl::ast::Type2::ChoiceFromInlineGroup { .. }
+            | cddl::ast::Type2::ChoiceFromGroup { .. } => {
+                writeln!(
+                    self.stderr,
+                    "In `{}`, `{}` could not be resolved; it is `z.unknown()`.",
+                    self.rule, t2
+                );
+                write!(self.stdout, "z.unknown()");
+            }
+            cddl::ast::Type2::TaggedData { tag, t, .. } => {
+                self.visit_tagged_data(tag, Some(t))?;
+            }
+            cddl::ast::Type2::DataMajorType { mt, constraint, .. } => {
+                self.visit_major_type(*mt, constraint)?;
+            }
             // The default has the correct behavior for the rest of the cases.
             t2 => {
                 cddl::visitor::walk_type2(self, t2)?;
@@ -772,8 +1644,13 @@ impl<'a, 'b: 'a, Stdout: Write, Stderr: Write> Visitor<'a, 'b, Error> for Engine
                 cddl::token::Value::INT(value) => write!(self.stdout, "z.literal({})", value),
                 cddl::token::Value::UINT(value) => write!(self.stdout, "z.literal({})", value),
                 cddl::token::Value::FLOAT(value) => write!(self.stdout, "z.literal({})", value),
-                cddl::token::Value::TEXT(value) => write!(self.stdout, "z.literal(\"{}\")", value),
-                cddl::token::Value::BYTE(value) => write!(self.stdout, "z.literal(\"{}\")", value),
+                cddl::token::Value::TEXT(value) => {
+                    write!(self.stdout, "z.literal({})", text_literal(value))
+                }
+                cddl::token::Value::BYTE(value) => {
+                    let schema = self.byte_value(value, ValueMode::Literal);
+                    write!(self.stdout, "{}", schema)
+                }
             },
             ValueMode::Generic => match value {
                 cddl::token::Value::INT(_) => write!(self.stdout, "z.number().int()"),
@@ -782,20 +1659,40 @@ impl<'a, 'b: 'a, Stdout: Write, Stderr: Write> Visitor<'a, 'b, Error> for Engine
                 }
                 cddl::token::Value::FLOAT(_) => write!(self.stdout, "z.number()"),
                 cddl::token::Value::TEXT(_) => write!(self.stdout, "z.string()"),
-                cddl::token::Value::BYTE(_) => write!(self.stdout, "z.string()"),
+                cddl::token::Value::BYTE(value) => {
+                    let schema = self.byte_value(value, ValueMode::Generic);
+                    write!(self.stdout, "{}", schema)
+                }
             },
             ValueMode::JavaScript => match value {
                 cddl::token::Value::INT(value) => write!(self.stdout, "{}", value),
                 cddl::token::Value::UINT(value) => write!(self.stdout, "{}", value),
                 cddl::token::Value::FLOAT(value) => write!(self.stdout, "{}", value),
-                cddl::token::Value::TEXT(value) => write!(self.stdout, "\"{}\"", value),
-                cddl::token::Value::BYTE(value) => write!(self.stdout, "\"{}\"", value),
+                cddl::token::Value::TEXT(value) => write!(self.stdout, "{}", text_literal(value)),
+                cddl::token::Value::BYTE(value) => {
+                    let value = self.byte_value(value, ValueMode::JavaScript);
+                    write!(self.stdout, "{}", value)
+                }
             },
         };
         Ok(())
     }
 }
 
+/// The `number` and `bigint` schemas of an integer type.
+fn int_schemas(ident: &str) -> Option<(&'static str, &'static str)> {
+    match ident {
+        "uint" => Some(("z.number().int().nonnegative()", "z.bigint().nonnegative()")),
+        "nint" => Some(("z.number().int().negative()", "z.bigint().negative()")),
+        "int" => Some(("z.number().int()", "z.bigint()")),
+        _ => None,
+    }
+}
+
+fn is_any_schema(schema: &str) -> bool {
+    matches!(schema, "z.any()" | "z.unknown()")
+}
+
 fn is_primitive_type(type2: &cddl::ast::Type2) -> bool {
     !matches!(
         type2,
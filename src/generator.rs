@@ -0,0 +1,159 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Output formats, behind a common [`Generator`] trait.
+//!
+//! The command line looks formats up by name in a [`Registry`]. Crates
+//! adding a format implement [`Generator`], register it next to the built-in
+//! ones and run [`crate::cli::main`] with the result.
+
+use std::{fmt, io::Write};
+
+use anyhow::Result;
+use cddl::ast::CDDL;
+
+use crate::{
+    engines::{rust::Rust, typescript::TypeScript, zod::Zod},
+    monomorphize::monomorphize,
+    options::Options,
+};
+
+/// A message about a conversion that still succeeded, e.g. a rule named
+/// differently because its identifier was already taken.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// An output format.
+pub trait Generator {
+    /// Converts `cddl`, parsed from `source`, and writes the result to `out`.
+    /// The comments of `source` document the output.
+    fn generate(
+        &self,
+        cddl: &CDDL,
+        source: &str,
+        options: &Options,
+        out: &mut dyn Write,
+    ) -> Result<Vec<Diagnostic>>;
+}
+
+/// Output formats by name.
+pub struct Registry {
+    entries: Vec<Entry>,
+}
+
+struct Entry {
+    name: String,
+    aliases: Vec<String>,
+    generator: Box<dyn Generator>,
+}
+
+impl Default for Registry {
+    /// A registry of the built-in formats, `typescript`, `zod` and `rust`.
+    fn default() -> Self {
+        let mut registry = Registry::new();
+        registry
+            .register("typescript", TypeScript)
+            .alias("typescript", "type-script")
+            .register("zod", Zod)
+            .register("rust", Rust);
+        registry
+    }
+}
+
+impl Registry {
+    /// An empty registry.
+    pub fn new() -> Registry {
+        Registry {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Registers `generator` as `name`, replacing the format of that name if
+    /// there is one.
+    pub fn register(&mut self, name: &str, generator: impl Generator + 'static) -> &mut Self {
+        let generator = Box::new(generator);
+        match self.entries.iter_mut().find(|entry| entry.name == name) {
+            Some(entry) => entry.generator = generator,
+            None => self.entries.push(Entry {
+                name: name.to_string(),
+                aliases: Vec::new(),
+                generator,
+            }),
+        }
+        self
+    }
+
+    /// Makes `alias` another name of the format `name`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no format is registered as `name`.
+    pub fn alias(&mut self, name: &str, alias: &str) -> &mut Self {
+        self.entries
+            .iter_mut()
+            .find(|entry| entry.name == name)
+            .unwrap_or_else(|| panic!("no format is registered as `{}`", name))
+            .aliases
+            .push(alias.to_string());
+        self
+    }
+
+    /// The format registered as `name` or with `name` as an alias.
+    pub fn get(&self, name: &str) -> Option<&dyn Generator> {
+        self.entries
+            .iter()
+            .find(|entry| entry.name == name || entry.aliases.iter().any(|alias| alias == name))
+            .map(|entry| entry.generator.as_ref())
+    }
+
+    /// The names of the formats with their aliases, in registration order.
+    pub fn names(&self) -> impl Iterator<Item = (&str, &[String])> {
+        self.entries
+            .iter()
+            .map(|entry| (entry.name.as_str(), entry.aliases.as_slice()))
+    }
+}
+
+/// Calls `generate` with `cddl`, or with an instance of it without generic
+/// rules if `options` ask for it.
+pub(crate) fn with_instances<T>(
+    cddl: &CDDL,
+    options: &Options,
+    generate: impl FnOnce(&CDDL) -> Result<T>,
+) -> Result<T> {
+    if !options.monomorphize {
+        return generate(cddl);
+    }
+    let mut names = Vec::new();
+    let cddl = monomorphize(cddl, &mut names).map_err(anyhow::Error::msg)?;
+    generate(&cddl)
+}
+
+/// The diagnostics an engine wrote, one per line.
+pub(crate) fn diagnostics(stderr: &[u8]) -> Vec<Diagnostic> {
+    String::from_utf8_lossy(stderr)
+        .lines()
+        .map(|line| Diagnostic {
+            message: line.to_string(),
+        })
+        .collect()
+}
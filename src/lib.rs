@@ -1,2 +1,23 @@
+pub mod amendments;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod build;
+#[cfg(all(feature = "cli", not(target_arch = "wasm32")))]
+pub mod cli;
+pub mod config;
+pub mod convert;
+pub mod docs;
 pub mod engines;
+pub mod generator;
+pub mod monomorphize;
+pub mod naming;
+pub mod options;
+pub mod printer;
+mod rewrite;
+pub mod unwrap;
 pub mod util;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod validate;
+pub mod vectors;
+
+pub use convert::{convert, Output, Target};
+pub use generator::{Diagnostic, Generator, Registry};
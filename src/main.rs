@@ -13,50 +13,8 @@
 // limitations under the License.
 
 use anyhow::Result;
-use cddl::visitor::Visitor;
-use clap::{Parser, ValueEnum};
-use std::path::PathBuf;
-
-#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
-enum EngineType {
-    TypeScript,
-    Zod,
-}
-
-/// Simple program to greet a person
-#[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
-struct Args {
-    /// File to convert.
-    file: PathBuf,
-    /// Format to output.
-    #[arg(short, long, value_enum, default_value_t = EngineType::TypeScript)]
-    format: EngineType,
-}
+use cddlconv::Registry;
 
 fn main() -> Result<()> {
-    let args = Args::parse();
-    let input = std::fs::read_to_string(args.file)?;
-    let cddl =
-        cddl::parser::cddl_from_str(&input, true).map_err(|error| anyhow::Error::msg(error))?;
-
-    match args.format {
-        EngineType::TypeScript => {
-            let mut engine = cddlconv::engines::typescript::Engine::with_writers(
-                std::io::stdout(),
-                std::io::stderr(),
-            );
-            engine.visit_cddl(&cddl)?;
-            engine.print_postamble();
-        }
-        EngineType::Zod => {
-            let mut engine =
-                cddlconv::engines::zod::Engine::with_writers(std::io::stdout(), std::io::stderr());
-            engine.print_preamble();
-            engine.visit_cddl(&cddl)?;
-            engine.print_postamble();
-        }
-    };
-
-    Ok(())
+    cddlconv::cli::main(&Registry::default())
 }
@@ -0,0 +1,442 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Instantiation of generic rules at each distinct set of arguments they are
+//! used with.
+//!
+//! For example, `message<"a", int>` becomes a reference to a new non-generic
+//! rule `message-a-int`, which engines emit as `MessageAInt`.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use cddl::{
+    ast::{
+        GenericArgs, GroupEntry, Rule, Type, Type1, Type2, TypeChoice, ValueMemberKeyEntry, CDDL,
+    },
+    Error,
+};
+
+use crate::rewrite::{walk_group_entry, walk_type2, Rewriter};
+
+/// Guards against generic rules that instantiate themselves with ever larger
+/// arguments (e.g. `t<x> = [* t<[x]>]`).
+const MAX_DEPTH: usize = 1 << 4;
+
+struct Instance<'a> {
+    name: String,
+    rules: Vec<Rule<'a>>,
+}
+
+/// Returns a copy of `cddl` without generic rules.
+///
+/// Every use of a generic rule is replaced by a reference to an instance of
+/// the rule with its parameters substituted. Instances are emitted in place of
+/// the generic rule; generic rules that are never used are dropped. The names
+/// of the instances are stored in `names`, which the result borrows from.
+pub fn monomorphize<'a>(cddl: &CDDL<'a>, names: &'a mut Vec<String>) -> Result<CDDL<'a>, Error> {
+    let mut generics: HashMap<&str, Vec<&Rule<'a>>> = HashMap::new();
+    let mut taken = HashSet::new();
+    for rule in &cddl.rules {
+        let (name, params) = rule_signature(rule);
+        taken.insert(name.to_string());
+        if params.is_some() {
+            generics.entry(name).or_default().push(rule);
+        }
+    }
+
+    let mut uses = Collector::default();
+    for rule in &cddl.rules {
+        if rule_signature(rule).1.is_none() {
+            uses.rewrite_rule(&mut rule.clone());
+        }
+    }
+
+    let mut instances: Vec<Instance<'a>> = Vec::new();
+    let mut keys: HashMap<String, usize> = HashMap::new();
+    let mut generic_instances: HashMap<&str, Vec<usize>> = HashMap::new();
+    while let Some((ident, args, depth)) = uses.references.pop_front() {
+        let Some(rules) = generics.get(ident) else {
+            continue;
+        };
+        let key = instance_key(ident, &args);
+        if keys.contains_key(&key) {
+            continue;
+        }
+        if depth == MAX_DEPTH {
+            return Err(Error::CDDL(format!(
+                "generic rule `{}` is instantiated too deeply; is it infinitely recursive?",
+                ident
+            )));
+        }
+
+        let mut instance = Instance {
+            name: instance_name(ident, &args, &mut taken),
+            rules: Vec::with_capacity(rules.len()),
+        };
+        for rule in rules {
+            let Some(mut rule) = instantiate(rule, &args) else {
+                return Err(Error::CDDL(format!(
+                    "`{}` expects {} generic arguments, got {}",
+                    ident,
+                    rule_signature(rule).1.as_ref().unwrap().params.len(),
+                    args.args.len()
+                )));
+            };
+            uses.depth = depth + 1;
+            uses.rewrite_rule(&mut rule);
+            instance.rules.push(rule);
+        }
+        keys.insert(key, instances.len());
+        generic_instances
+            .entry(ident)
+            .or_default()
+            .push(instances.len());
+        instances.push(instance);
+    }
+
+    let offset = names.len();
+    names.extend(instances.iter().map(|instance| instance.name.clone()));
+    let names: &'a [String] = &names[offset..];
+    let mut renaming = Renaming {
+        names: keys
+            .into_iter()
+            .map(|(key, index)| (key, names[index].as_str()))
+            .collect(),
+    };
+
+    let mut rules = Vec::with_capacity(cddl.rules.len());
+    let mut emitted = HashSet::new();
+    for rule in &cddl.rules {
+        let (name, params) = rule_signature(rule);
+        if params.is_none() {
+            let mut rule = rule.clone();
+            renaming.rewrite_rule(&mut rule);
+            rules.push(rule);
+            continue;
+        }
+        if !emitted.insert(name) {
+            continue;
+        }
+        for &index in generic_instances.get(name).into_iter().flatten() {
+            for rule in &instances[index].rules {
+                let mut rule = rule.clone();
+                renaming.rewrite_rule(&mut rule);
+                match &mut rule {
+                    Rule::Type { rule, .. } => {
+                        rule.name.ident = names[index].as_str();
+                        rule.generic_params = None;
+                    }
+                    Rule::Group { rule, .. } => {
+                        rule.name.ident = names[index].as_str();
+                        rule.generic_params = None;
+                    }
+                }
+                rules.push(rule);
+            }
+        }
+    }
+
+    Ok(CDDL {
+        rules,
+        comments: cddl.comments.clone(),
+    })
+}
+
+fn rule_signature<'r, 'a>(
+    rule: &'r Rule<'a>,
+) -> (&'a str, &'r Option<cddl::ast::GenericParams<'a>>) {
+    match rule {
+        Rule::Type { rule, .. } => (rule.name.ident, &rule.generic_params),
+        Rule::Group { rule, .. } => (rule.name.ident, &rule.generic_params),
+    }
+}
+
+/// A copy of the generic `rule` with its parameters replaced by `args`, or
+/// `None` if the numbers of parameters and arguments differ.
+pub(crate) fn instantiate<'a>(rule: &Rule<'a>, args: &GenericArgs<'a>) -> Option<Rule<'a>> {
+    let params = rule_signature(rule).1.as_ref()?;
+    if params.params.len() != args.args.len() {
+        return None;
+    }
+    let mut substitution = Substitution {
+        params: params
+            .params
+            .iter()
+            .zip(&args.args)
+            .map(|(param, arg)| (param.param.ident, arg.arg.as_ref()))
+            .collect(),
+    };
+    let mut rule = rule.clone();
+    substitution.rewrite_rule(&mut rule);
+    Some(rule)
+}
+
+fn instance_key(ident: &str, args: &GenericArgs) -> String {
+    format!("{}{}", ident, args)
+}
+
+/// Derives a readable CDDL name from the arguments, e.g. `message-a-int` for
+/// `message<"a", int>`.
+fn instance_name(ident: &str, args: &GenericArgs, taken: &mut HashSet<String>) -> String {
+    let mut name = ident.to_string();
+    for arg in &args.args {
+        for part in arg_name(&arg.arg).split(|ch: char| !ch.is_ascii_alphanumeric()) {
+            if !part.is_empty() {
+                name.push('-');
+                name.push_str(part);
+            }
+        }
+    }
+    let mut candidate = name.clone();
+    let mut suffix = 1;
+    while !taken.insert(candidate.clone()) {
+        suffix += 1;
+        candidate = format!("{}-{}", name, suffix);
+    }
+    candidate
+}
+
+fn arg_name(t1: &Type1) -> String {
+    if t1.operator.is_some() {
+        return t1.to_string();
+    }
+    match &t1.type2 {
+        Type2::Typename {
+            ident,
+            generic_args: Some(args),
+            ..
+        } => std::iter::once(ident.ident.to_string())
+            .chain(args.args.iter().map(|arg| arg_name(&arg.arg)))
+            .collect::<Vec<_>>()
+            .join("-"),
+        Type2::Typename { ident, .. } => ident.ident.to_string(),
+        Type2::TextValue { value, .. } => value.to_string(),
+        Type2::IntValue { value, .. } => value.to_string(),
+        Type2::UintValue { value, .. } => value.to_string(),
+        Type2::FloatValue { value, .. } => value.to_string(),
+        t2 => t2.to_string(),
+    }
+}
+
+/// Collects references to rules with generic arguments.
+#[derive(Default)]
+struct Collector<'a> {
+    references: VecDeque<(&'a str, GenericArgs<'a>, usize)>,
+    /// Number of instantiations leading to the rule being collected.
+    depth: usize,
+}
+
+impl<'a> Rewriter<'a> for Collector<'a> {
+    fn rewrite_type2(&mut self, t2: &mut Type2<'a>) {
+        match t2 {
+            Type2::Typename {
+                ident,
+                generic_args: Some(args),
+                ..
+            }
+            | Type2::Unwrap {
+                ident,
+                generic_args: Some(args),
+                ..
+            }
+            | Type2::ChoiceFromGroup {
+                ident,
+                generic_args: Some(args),
+                ..
+            } => self
+                .references
+                .push_back((ident.ident, args.clone(), self.depth)),
+            t2 => walk_type2(self, t2),
+        }
+    }
+
+    fn rewrite_group_entry(&mut self, entry: &mut GroupEntry<'a>) {
+        match entry {
+            GroupEntry::TypeGroupname { ge, .. } if ge.generic_args.is_some() => self
+                .references
+                .push_back((ge.name.ident, ge.generic_args.clone().unwrap(), self.depth)),
+            entry => walk_group_entry(self, entry),
+        }
+    }
+}
+
+/// Replaces generic parameters with their arguments.
+struct Substitution<'s, 'a> {
+    params: HashMap<&'a str, &'s Type1<'a>>,
+}
+
+impl<'a> Substitution<'_, 'a> {
+    fn lookup(&self, t2: &Type2<'a>) -> Option<&Type1<'a>> {
+        match t2 {
+            Type2::Typename {
+                ident,
+                generic_args: None,
+                ..
+            } if ident.socket.is_none() => self.params.get(ident.ident).copied(),
+            _ => None,
+        }
+    }
+}
+
+impl<'a> Rewriter<'a> for Substitution<'_, 'a> {
+    fn rewrite_type1(&mut self, t1: &mut Type1<'a>) {
+        if t1.operator.is_none() {
+            if let Some(arg) = self.lookup(&t1.type2) {
+                *t1 = arg.clone();
+                return;
+            }
+        }
+        self.rewrite_type2(&mut t1.type2);
+        if let Some(operator) = &mut t1.operator {
+            self.rewrite_type2(&mut operator.type2);
+        }
+    }
+
+    fn rewrite_type2(&mut self, t2: &mut Type2<'a>) {
+        let Some(arg) = self.lookup(t2) else {
+            walk_type2(self, t2);
+            return;
+        };
+        *t2 = if arg.operator.is_none() {
+            arg.type2.clone()
+        } else {
+            // Keep the operator of the argument from binding to the operator
+            // applied to the parameter.
+            Type2::ParenthesizedType {
+                pt: Type {
+                    type_choices: vec![TypeChoice {
+                        type1: arg.clone(),
+                        comments_before_type: None,
+                        comments_after_type: None,
+                    }],
+                    span: arg.span,
+                },
+                span: arg.span,
+                comments_before_type: None,
+                comments_after_type: None,
+            }
+        };
+    }
+
+    fn rewrite_group_entry(&mut self, entry: &mut GroupEntry<'a>) {
+        let arg = match entry {
+            GroupEntry::TypeGroupname { ge, .. }
+                if ge.generic_args.is_none() && ge.name.socket.is_none() =>
+            {
+                self.params.get(ge.name.ident).copied()
+            }
+            _ => None,
+        };
+        let Some(arg) = arg else {
+            walk_group_entry(self, entry);
+            return;
+        };
+        let GroupEntry::TypeGroupname {
+            ge,
+            span,
+            leading_comments,
+            trailing_comments,
+        } = entry
+        else {
+            unreachable!();
+        };
+        match &arg.type2 {
+            Type2::Typename {
+                ident,
+                generic_args,
+                ..
+            } if arg.operator.is_none() => {
+                ge.name = ident.clone();
+                ge.generic_args = generic_args.clone();
+            }
+            _ => {
+                *entry = GroupEntry::ValueMemberKey {
+                    ge: Box::new(ValueMemberKeyEntry {
+                        occur: ge.occur.clone(),
+                        member_key: None,
+                        entry_type: Type {
+                            type_choices: vec![TypeChoice {
+                                type1: arg.clone(),
+                                comments_before_type: None,
+                                comments_after_type: None,
+                            }],
+                            span: arg.span,
+                        },
+                    }),
+                    span: *span,
+                    leading_comments: leading_comments.take(),
+                    trailing_comments: trailing_comments.take(),
+                }
+            }
+        }
+    }
+}
+
+/// Points references with generic arguments at the instances.
+struct Renaming<'a> {
+    names: HashMap<String, &'a str>,
+}
+
+impl<'a> Renaming<'a> {
+    fn rename(
+        &self,
+        ident: &mut cddl::ast::Identifier<'a>,
+        generic_args: &mut Option<GenericArgs<'a>>,
+    ) -> bool {
+        let Some(args) = generic_args else {
+            return false;
+        };
+        let Some(&name) = self.names.get(&instance_key(ident.ident, args)) else {
+            return false;
+        };
+        ident.ident = name;
+        *generic_args = None;
+        true
+    }
+}
+
+impl<'a> Rewriter<'a> for Renaming<'a> {
+    fn rewrite_type2(&mut self, t2: &mut Type2<'a>) {
+        let renamed = match t2 {
+            Type2::Typename {
+                ident,
+                generic_args,
+                ..
+            }
+            | Type2::Unwrap {
+                ident,
+                generic_args,
+                ..
+            }
+            | Type2::ChoiceFromGroup {
+                ident,
+                generic_args,
+                ..
+            } => self.rename(ident, generic_args),
+            _ => false,
+        };
+        if !renamed {
+            walk_type2(self, t2);
+        }
+    }
+
+    fn rewrite_group_entry(&mut self, entry: &mut GroupEntry<'a>) {
+        if let GroupEntry::TypeGroupname { ge, .. } = entry {
+            if self.rename(&mut ge.name, &mut ge.generic_args) {
+                return;
+            }
+        }
+        walk_group_entry(self, entry);
+    }
+}
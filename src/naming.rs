@@ -0,0 +1,319 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Identifiers of the generated code.
+//!
+//! Case conversion may turn different CDDL names into the same identifier
+//! (e.g. `foo-bar`, `foo_bar` and `FooBar` all become `FooBar`), or into one
+//! that is not valid in the target language. [`Names`] decides the identifier
+//! of every rule up front: characters that identifiers cannot have become
+//! `_`, reserved words get a trailing `_`, and identifiers already taken by
+//! an earlier rule, or by a name derived from one (e.g. the `FooVector` tuple
+//! of `foo`), get a number, which is reported.
+
+use std::collections::HashMap;
+
+use cddl::ast::{Rule, CDDL};
+
+use crate::{options::Renames, util::to_pascalcase};
+
+/// Words reserved by JavaScript and TypeScript, which no identifier may be.
+pub const RESERVED_WORDS: &[&str] = &[
+    "arguments",
+    "await",
+    "break",
+    "case",
+    "catch",
+    "class",
+    "const",
+    "continue",
+    "debugger",
+    "default",
+    "delete",
+    "do",
+    "else",
+    "enum",
+    "eval",
+    "export",
+    "extends",
+    "false",
+    "finally",
+    "for",
+    "function",
+    "if",
+    "implements",
+    "import",
+    "in",
+    "instanceof",
+    "interface",
+    "let",
+    "new",
+    "null",
+    "package",
+    "private",
+    "protected",
+    "public",
+    "return",
+    "static",
+    "super",
+    "switch",
+    "this",
+    "throw",
+    "true",
+    "try",
+    "typeof",
+    "var",
+    "void",
+    "while",
+    "with",
+    "yield",
+];
+
+/// A name derived from the identifier of a rule, as the prefix and the suffix
+/// of its last part, e.g. `("is", "")` for the `isFoo` type guard of `Foo`.
+pub type Affix = (&'static str, &'static str);
+
+/// Identifiers of the rules of a CDDL document.
+#[derive(Debug, Clone, Default)]
+pub struct Names {
+    renames: Renames,
+    /// Identifiers reserved by the engine, on top of [`RESERVED_WORDS`].
+    reserved: &'static [&'static str],
+    /// Identifiers of the rules, keyed by CDDL name.
+    rules: HashMap<String, String>,
+    /// CDDL names of the rules, keyed by their identifiers and the names
+    /// derived from them.
+    owners: HashMap<String, String>,
+}
+
+impl Names {
+    /// Names the rules of `cddl` in order, along with the names `derived`
+    /// from each, keyed by CDDL name. Also returns a message for every rule
+    /// named differently because one of its names was already taken.
+    pub fn new(
+        cddl: &CDDL,
+        renames: &Renames,
+        reserved: &'static [&'static str],
+        derived: impl Fn(&str) -> Vec<Affix>,
+    ) -> (Names, Vec<String>) {
+        let mut names = Names {
+            renames: renames.clone(),
+            reserved,
+            rules: HashMap::new(),
+            owners: HashMap::new(),
+        };
+        let mut idents = Vec::new();
+        for rule in &cddl.rules {
+            let ident = match rule {
+                Rule::Type { rule, .. } => rule.name.ident,
+                Rule::Group { rule, .. } => rule.name.ident,
+            };
+            if !idents.contains(&ident) {
+                idents.push(ident);
+            }
+        }
+        let mut owners = HashMap::new();
+        let (identifiers, collisions) = number_duplicates(
+            &mut owners,
+            idents
+                .iter()
+                .map(|ident| (*ident, names.sanitize(&renames.rule(ident)), derived(ident))),
+        );
+        names.owners = owners;
+        names.rules = idents
+            .into_iter()
+            .map(String::from)
+            .zip(identifiers)
+            .collect();
+        (names, collisions)
+    }
+
+    /// Names the members of an enum of the rule `ident` after their values.
+    /// Also returns a message for every member named differently because its
+    /// name was already taken.
+    pub fn members<'v>(
+        &self,
+        ident: &str,
+        values: impl IntoIterator<Item = &'v str>,
+    ) -> (Vec<String>, Vec<String>) {
+        let (members, collisions) = number_duplicates(
+            &mut HashMap::new(),
+            values
+                .into_iter()
+                .map(|value| (value, self.identifier(&to_words(value)), Vec::new())),
+        );
+        let collisions = collisions
+            .into_iter()
+            .map(|collision| format!("In `{}`, {}", ident, collision))
+            .collect();
+        (members, collisions)
+    }
+
+    /// Names the variants of the rule `ident` after their values, e.g.
+    /// `MessageSuccess` for `"success"` in `message`, which type guards are
+    /// named after (`isMessageSuccess`). Values that make no identifier are
+    /// named after their position instead (e.g. `MessageVariant2`). Names of
+    /// rules, names derived from them and names in `taken` get a number, and
+    /// are added to `taken`. Also
+    /// returns a message for every variant named differently.
+    pub fn variants<'v>(
+        &self,
+        ident: &str,
+        values: impl IntoIterator<Item = &'v str>,
+        taken: &mut HashMap<String, String>,
+    ) -> (Vec<String>, Vec<String>) {
+        let (namespaces, name) = self.split_rule(ident);
+        for (taken_name, source) in &self.owners {
+            let (rule_namespaces, rule_name) =
+                taken_name.rsplit_once('.').unwrap_or(("", taken_name));
+            if rule_namespaces == namespaces.join(".") {
+                taken.insert(rule_name.to_string(), source.clone());
+            }
+        }
+        let (variants, collisions) = number_duplicates(
+            taken,
+            values.into_iter().enumerate().map(|(index, value)| {
+                let suffix = to_words(value.trim_matches('"'));
+                let variant = format!("{}{}", name, suffix);
+                if suffix.is_empty() || identifier(&variant, &[]) != variant {
+                    (value, format!("{}Variant{}", name, index + 1), Vec::new())
+                } else {
+                    (value, variant, Vec::new())
+                }
+            }),
+        );
+        let collisions = collisions
+            .into_iter()
+            .map(|collision| format!("In `{}`, {}", ident, collision))
+            .collect();
+        (variants, collisions)
+    }
+
+    /// The identifier of a rule, e.g. `BrowsingContext.Info` for
+    /// `browsingContext.Info`.
+    pub fn rule(&self, ident: &str) -> String {
+        self.rules
+            .get(ident)
+            .cloned()
+            .unwrap_or_else(|| self.sanitize(&self.renames.rule(ident)))
+    }
+
+    /// Like [`Names::rule`], but split into the namespaces and the name.
+    pub fn split_rule(&self, ident: &str) -> (Vec<String>, String) {
+        let mut parts = self
+            .rule(ident)
+            .split('.')
+            .map(String::from)
+            .collect::<Vec<_>>();
+        let name = parts.pop().unwrap();
+        (parts, name)
+    }
+
+    /// Makes `name` a valid identifier that is not reserved.
+    pub fn identifier(&self, name: &str) -> String {
+        identifier(name, self.reserved)
+    }
+
+    fn sanitize(&self, name: &str) -> String {
+        name.split('.')
+            .map(|part| self.identifier(part))
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+}
+
+/// Makes `name` a valid identifier that is neither one of the
+/// [`RESERVED_WORDS`] nor of `reserved`.
+pub fn identifier(name: &str, reserved: &[&str]) -> String {
+    let mut identifier = name
+        .chars()
+        .map(|ch| {
+            if ch.is_alphanumeric() || ch == '_' || ch == '$' {
+                ch
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    if identifier.is_empty() || identifier.starts_with(|ch: char| ch.is_ascii_digit()) {
+        identifier.insert(0, '_');
+    }
+    if RESERVED_WORDS.contains(&identifier.as_str()) || reserved.contains(&identifier.as_str()) {
+        identifier.push('_');
+    }
+    identifier
+}
+
+/// Converts `value` to PascalCase like the names of rules, with the
+/// characters identifiers cannot have separating words, e.g. `ItSQuoted` for
+/// `it's "quoted"`.
+pub fn to_words(value: &str) -> String {
+    to_pascalcase(
+        value
+            .chars()
+            .map(|ch| if ch.is_alphanumeric() { ch } else { ' ' })
+            .collect::<String>(),
+    )
+}
+
+/// The name derived from `name` by `affix`, e.g. `BrowsingContext.isInfo`
+/// for `BrowsingContext.Info` and `("is", "")`.
+fn derive(name: &str, (prefix, suffix): &Affix) -> String {
+    match name.rsplit_once('.') {
+        Some((namespaces, name)) => format!("{}.{}{}{}", namespaces, prefix, name, suffix),
+        None => format!("{}{}{}", prefix, name, suffix),
+    }
+}
+
+/// Numbers the names of `items`, given as `(source, name, affixes)`, of which
+/// an earlier item or `owners` already has the name or one derived from it
+/// by the affixes, e.g. the second `FooBar` becomes `FooBar2`. Also returns a
+/// message for every item named differently.
+fn number_duplicates<'s>(
+    owners: &mut HashMap<String, String>,
+    items: impl IntoIterator<Item = (&'s str, String, Vec<Affix>)>,
+) -> (Vec<String>, Vec<String>) {
+    let mut names = Vec::new();
+    let mut collisions = Vec::new();
+    for (source, name, affixes) in items {
+        let all_names = |name: &str| {
+            std::iter::once(name.to_string())
+                .chain(affixes.iter().map(|affix| derive(name, affix)))
+                .collect::<Vec<_>>()
+        };
+        let taken = all_names(&name)
+            .into_iter()
+            .find(|name| owners.contains_key(name));
+        let mut unique = name.clone();
+        let mut number = 1;
+        while all_names(&unique)
+            .iter()
+            .any(|name| owners.contains_key(name))
+        {
+            number += 1;
+            unique = format!("{}{}", name, number);
+        }
+        if let Some(taken) = taken {
+            collisions.push(format!(
+                "`{}` is named `{}` since `{}` is the name of `{}`",
+                source, unique, taken, owners[&taken]
+            ));
+        }
+        for name in all_names(&unique) {
+            owners.insert(name, source.to_string());
+        }
+        names.push(unique);
+    }
+    (names, collisions)
+}
@@ -0,0 +1,236 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+
+use crate::util::to_namespaced;
+
+/// Options controlling the output of the engines.
+///
+/// Options that only make sense for a single engine are ignored by the others.
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    /// Emit template literal types (e.g. `` `${number}.${number}` ``) for
+    /// simple `.regexp`/`.pcre` patterns instead of `string`.
+    ///
+    /// TypeScript only.
+    pub template_literals: bool,
+    /// Emit `isFoo(value: unknown): value is Foo` type guards for every rule.
+    ///
+    /// TypeScript only.
+    pub type_guards: bool,
+    /// Rules whose primitive aliases (e.g. `id = text`) are emitted as branded
+    /// types so that they are not interchangeable with each other.
+    pub brands: Brands,
+    /// Represent CBOR tags without a well-known mapping as `{tag, value}`
+    /// objects instead of just their content.
+    pub cbor_tags: bool,
+    /// Selects the representation of bytes and integers.
+    pub profile: Profile,
+    /// Instantiate generic rules at each set of arguments they are used with
+    /// (see [`crate::monomorphize`]) instead of emitting generics.
+    ///
+    /// Applied to the CDDL before it is handed to an engine.
+    pub monomorphize: bool,
+    /// Selects how maps with and without catch-all members (e.g.
+    /// `* tstr => any`) are emitted.
+    pub extensibility: Extensibility,
+    /// Selects how rules that are a choice of text values (or of the values
+    /// of a group, e.g. `&statuses`) are emitted.
+    ///
+    /// TypeScript only.
+    pub enums: Enums,
+    /// Emit a tuple (e.g. `PairVector`) for every group rule used as elements
+    /// of an array (e.g. `[* pair]`), which such arrays are made of (see
+    /// [`crate::vectors`]).
+    pub vector_groups: bool,
+    /// Layout of the generated code.
+    pub layout: Layout,
+    /// Names to use instead of the ones derived from the CDDL.
+    pub renames: Renames,
+}
+
+/// Selects the representation of primitive types to match what a decoder
+/// returns.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Profile {
+    /// Matches `JSON.parse`: byte strings are (base64) strings and all
+    /// integers, including bignums, are numbers.
+    #[default]
+    Json,
+    /// Matches a CBOR decoder: byte strings are `Uint8Array`s and integers
+    /// that may exceed the safe integer range are `bigint`s.
+    Cbor,
+}
+
+/// Selects how catch-all members (e.g. `* tstr => any`) of maps that also
+/// have named members are emitted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Extensibility {
+    /// Catch-all members become an index signature (TypeScript) or a
+    /// catch-all schema (Zod) on the object itself.
+    #[default]
+    Open,
+    /// Catch-all members are dropped, so maps only have their named members.
+    Closed,
+    /// Like [`Extensibility::Open`], but maps without catch-all members
+    /// reject unknown members. Zod only.
+    Strict,
+}
+
+/// Selects the representation of enums, e.g. `mode = "cors" / "no-cors"`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Enums {
+    /// `export const enum Mode { Cors = "cors", NoCors = "no-cors" }`, which
+    /// tools compiling files in isolation (e.g. Babel or esbuild) cannot
+    /// inline.
+    #[default]
+    ConstEnum,
+    /// `export enum Mode { Cors = "cors", NoCors = "no-cors" }`.
+    Enum,
+    /// `export type Mode = "cors" | "no-cors"`.
+    Union,
+    /// `export const Mode = { Cors: "cors", NoCors: "no-cors" } as const`,
+    /// along with `export type Mode` for the union of its values.
+    Object,
+}
+
+/// Layout of the generated code (see [`crate::printer`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Layout {
+    /// Number of spaces per level of indentation.
+    pub indent_width: usize,
+    /// Width that lines are kept within where possible.
+    pub line_width: usize,
+    pub trailing_commas: TrailingCommas,
+    pub quotes: Quotes,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Layout {
+            indent_width: 2,
+            line_width: 80,
+            trailing_commas: TrailingCommas::default(),
+            quotes: Quotes::default(),
+        }
+    }
+}
+
+/// Selects whether lists broken over several lines end with a comma.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TrailingCommas {
+    #[default]
+    All,
+    None,
+}
+
+/// Selects the quotes of string literals.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Quotes {
+    #[default]
+    Double,
+    Single,
+}
+
+/// Selects the rules that are emitted as branded types.
+#[derive(Debug, Clone, Default)]
+pub enum Brands {
+    #[default]
+    None,
+    /// Brand every primitive alias rule.
+    All,
+    /// Brand only the primitive alias rules with the given CDDL names.
+    Only(Vec<String>),
+}
+
+impl Brands {
+    pub fn contains(&self, name: &str) -> bool {
+        match self {
+            Brands::None => false,
+            Brands::All => true,
+            Brands::Only(names) => names.iter().any(|brand| brand == name),
+        }
+    }
+}
+
+/// Names of rules and members in the generated code, instead of the ones
+/// derived from the CDDL.
+///
+/// ```toml
+/// [rules]
+/// js-uint = "JsSafeUint"
+/// "browsingContext.BrowsingContext" = "BrowsingContextId"
+///
+/// [properties."browsingContext.Info"]
+/// context = "browsingContextId"
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Renames {
+    /// Names of rules, keyed by their CDDL name. Names are used as they are,
+    /// with `.` separating namespaces.
+    #[serde(default)]
+    pub rules: BTreeMap<String, String>,
+    /// Names of members, keyed by the CDDL name of the rule they are written
+    /// in and then by their key. Values are still (de)serialized under their
+    /// key: Zod schemas rename the members of what they parse.
+    #[serde(default)]
+    pub properties: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl Renames {
+    /// The name of a rule, e.g. `BrowsingContext.Info` for
+    /// `browsingContext.Info`.
+    pub fn rule(&self, ident: &str) -> String {
+        self.rules
+            .get(ident)
+            .cloned()
+            .unwrap_or_else(|| to_namespaced(ident))
+    }
+
+    /// Like [`Renames::rule`], but split into the namespaces and the name.
+    pub fn split_rule(&self, ident: &str) -> (Vec<String>, String) {
+        let mut parts = self
+            .rule(ident)
+            .split('.')
+            .map(String::from)
+            .collect::<Vec<_>>();
+        let name = parts.pop().unwrap();
+        (parts, name)
+    }
+
+    /// The name of the member with the given key in a rule, if it is renamed.
+    pub fn property(&self, rule: &str, key: &str) -> Option<&str> {
+        self.properties
+            .get(rule)
+            .and_then(|properties| properties.get(key))
+            .map(String::as_str)
+    }
+
+    /// Adds the renames of `other`, which take precedence.
+    pub fn extend(&mut self, other: Renames) {
+        self.rules.extend(other.rules);
+        for (rule, properties) in other.properties {
+            self.properties.entry(rule).or_default().extend(properties);
+        }
+    }
+}
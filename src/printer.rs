@@ -0,0 +1,657 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Layout of the generated code.
+//!
+//! Engines write code without minding whitespace. The code is split into
+//! tokens and laid out again: a group in brackets stays on one line if it
+//! fits, and is otherwise broken after each `,` or `;` (or before each `|`,
+//! `&`, `&&` and `||` if it has neither) with its content indented. The sole
+//! content of parentheses and square brackets hugs them instead, so that
+//! `z.lazy(() => z.object({` stays on one line.
+
+use std::io::{self, Write};
+
+use crate::options::{Layout, Quotes, TrailingCommas};
+
+/// Operators made of several characters, longest first.
+const OPERATORS: [&str; 10] = [
+    "===", "!==", "...", "=>", "==", "!=", ">=", "<=", "&&", "||",
+];
+
+/// Binary operators, which are surrounded by spaces.
+const BINARY_OPERATORS: [&str; 12] = [
+    "=", "=>", "|", "&", "===", "!==", "==", "!=", ">=", "<=", "&&", "||",
+];
+
+/// Operators that a group is broken before if it has no other separator.
+const LEADING_OPERATORS: [&str; 4] = ["|", "&", "&&", "||"];
+
+/// A writer that lays out the code written to it when it is flushed, turned
+/// back into the inner writer or dropped.
+///
+/// Since the layout of a group depends on all of its content, flushing lays
+/// out what has been written so far on its own.
+pub struct Printer<W: Write> {
+    inner: Option<W>,
+    buffer: Vec<u8>,
+    layout: Layout,
+}
+
+impl<W: Write> Printer<W> {
+    pub fn new(inner: W) -> Self {
+        Printer {
+            inner: Some(inner),
+            buffer: Vec::new(),
+            layout: Layout::default(),
+        }
+    }
+    pub fn set_layout(&mut self, layout: Layout) {
+        self.layout = layout;
+    }
+    /// Lays out the code written so far and returns the inner writer.
+    pub fn into_inner(mut self) -> W {
+        let _ = self.print();
+        self.inner.take().unwrap()
+    }
+    /// Takes the code written so far as it was written.
+    pub(crate) fn take_unformatted(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.buffer)
+    }
+    fn print(&mut self) -> io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let code = String::from_utf8_lossy(&self.buffer).into_owned();
+        self.buffer.clear();
+        let inner = self.inner.as_mut().unwrap();
+        inner.write_all(format(&code, &self.layout).as_bytes())
+    }
+}
+
+impl<W: Write> Write for Printer<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.print()?;
+        self.inner.as_mut().unwrap().flush()
+    }
+}
+
+impl<W: Write> Drop for Printer<W> {
+    fn drop(&mut self) {
+        if self.inner.is_some() {
+            let _ = self.print();
+        }
+    }
+}
+
+/// Lays out TypeScript code.
+pub fn format(code: &str, layout: &Layout) -> String {
+    let mut writer = Writer {
+        layout,
+        flat: false,
+        out: String::new(),
+        column: 0,
+        depth: 0,
+        prev: None,
+        rest: 0,
+    };
+    writer.nodes(&parse(tokenize(code)), Separator::Statement, None);
+    if code.trim_end_matches([' ', '\t']).ends_with('\n') {
+        writer.newline();
+    }
+    writer.out
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Word,
+    Str,
+    Template,
+    Punct,
+    Comment,
+    LineComment,
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: Kind,
+    text: String,
+    /// Whether the token was preceded by whitespace.
+    space_before: bool,
+    /// Whether the token was preceded by an empty line.
+    blank_line_before: bool,
+}
+
+impl Token {
+    fn is(&self, punct: &str) -> bool {
+        self.kind == Kind::Punct && self.text == punct
+    }
+    fn is_any(&self, puncts: &[&str]) -> bool {
+        self.kind == Kind::Punct && puncts.contains(&self.text.as_str())
+    }
+    fn is_wordlike(&self) -> bool {
+        matches!(self.kind, Kind::Word | Kind::Str | Kind::Template)
+    }
+}
+
+enum Node {
+    Token(Token),
+    Group(Group),
+}
+
+impl Node {
+    fn first_token(&self) -> &Token {
+        match self {
+            Node::Token(token) => token,
+            Node::Group(group) => &group.open,
+        }
+    }
+    fn is(&self, punct: &str) -> bool {
+        matches!(self, Node::Token(token) if token.is(punct))
+    }
+    fn is_comment(&self) -> bool {
+        matches!(self, Node::Token(token) if matches!(token.kind, Kind::Comment | Kind::LineComment))
+    }
+}
+
+/// Tokens in brackets.
+struct Group {
+    open: Token,
+    children: Vec<Node>,
+    /// Missing if the brackets are not closed.
+    close: Option<Token>,
+}
+
+impl Group {
+    /// Whether the group may be broken over several lines to fit. Type
+    /// arguments never are.
+    fn can_break(&self) -> bool {
+        !self.open.is("<")
+            && (self.open.is("{") || Separator::of(&self.children) != Separator::None)
+    }
+}
+
+/// Where a sequence of nodes is broken into lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Separator {
+    /// After each `;`.
+    Statement,
+    /// After each `,`.
+    List,
+    /// Before each of the [`LEADING_OPERATORS`].
+    Operator,
+    None,
+}
+
+impl Separator {
+    fn of(nodes: &[Node]) -> Self {
+        if nodes
+            .iter()
+            .enumerate()
+            .any(|(index, node)| node.is(";") || ends_block(nodes, index))
+        {
+            Separator::Statement
+        } else if nodes.iter().any(|node| node.is(",")) {
+            Separator::List
+        } else if nodes
+            .iter()
+            .skip(1)
+            .any(|node| matches!(node, Node::Token(token) if token.is_any(&LEADING_OPERATORS)))
+        {
+            Separator::Operator
+        } else {
+            Separator::None
+        }
+    }
+}
+
+fn tokenize(code: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut offset = 0;
+    let mut space_before = false;
+    let mut newlines = 0;
+    while let Some(ch) = code[offset..].chars().next() {
+        let rest = &code[offset..];
+        if ch.is_whitespace() {
+            space_before = true;
+            if ch == '\n' {
+                newlines += 1;
+            }
+            offset += ch.len_utf8();
+            continue;
+        }
+        let (kind, len) = if rest.starts_with("/*") {
+            (
+                Kind::Comment,
+                rest.find("*/").map_or(rest.len(), |end| end + 2),
+            )
+        } else if rest.starts_with("//") {
+            (Kind::LineComment, rest.find('\n').unwrap_or(rest.len()))
+        } else if ch == '"' || ch == '\'' {
+            (Kind::Str, string_len(rest))
+        } else if ch == '`' {
+            (Kind::Template, template_len(rest))
+        } else if ch.is_ascii_digit() {
+            (Kind::Word, number_len(rest))
+        } else if is_word_char(ch) {
+            (
+                Kind::Word,
+                rest.find(|ch| !is_word_char(ch)).unwrap_or(rest.len()),
+            )
+        } else {
+            let len = OPERATORS
+                .iter()
+                .find(|operator| rest.starts_with(*operator))
+                .map_or(ch.len_utf8(), |operator| operator.len());
+            (Kind::Punct, len)
+        };
+        tokens.push(Token {
+            kind,
+            text: rest[..len].to_string(),
+            space_before,
+            blank_line_before: newlines > 1,
+        });
+        offset += len;
+        space_before = false;
+        newlines = 0;
+    }
+    tokens
+}
+
+fn is_word_char(ch: char) -> bool {
+    ch.is_alphanumeric() || ch == '_' || ch == '$'
+}
+
+fn string_len(rest: &str) -> usize {
+    let quote = rest.chars().next().unwrap();
+    let mut escaped = false;
+    for (offset, ch) in rest.char_indices().skip(1) {
+        match ch {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '\n' => return offset,
+            ch if ch == quote => return offset + 1,
+            _ => {}
+        }
+    }
+    rest.len()
+}
+
+fn template_len(rest: &str) -> usize {
+    let mut escaped = false;
+    let mut depth = 0;
+    let mut prev = '`';
+    for (offset, ch) in rest.char_indices().skip(1) {
+        match ch {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '{' if prev == '$' || depth > 0 => depth += 1,
+            '}' if depth > 0 => depth -= 1,
+            '`' if depth == 0 => return offset + 1,
+            _ => {}
+        }
+        prev = ch;
+    }
+    rest.len()
+}
+
+fn number_len(rest: &str) -> usize {
+    let mut prev = '0';
+    for (offset, ch) in rest.char_indices() {
+        let is_part = is_word_char(ch)
+            || (ch == '.' && rest[offset + 1..].starts_with(|ch: char| ch.is_ascii_digit()))
+            || ((ch == '-' || ch == '+') && matches!(prev, 'e' | 'E') && !rest.starts_with("0x"));
+        if !is_part {
+            return offset;
+        }
+        prev = ch;
+    }
+    rest.len()
+}
+
+fn parse(tokens: Vec<Token>) -> Vec<Node> {
+    let mut parents: Vec<(Token, Vec<Node>)> = Vec::new();
+    let mut nodes = Vec::new();
+    let mut after_word = false;
+    for token in tokens {
+        // Type arguments follow a name right away, unlike comparisons.
+        let is_type_arguments = token.is("<") && after_word && !token.space_before;
+        after_word = token.kind == Kind::Word;
+        if token.is_any(&["(", "[", "{"]) || is_type_arguments {
+            parents.push((token, std::mem::take(&mut nodes)));
+            continue;
+        }
+        let closes = parents.last().is_some_and(|(open, _)| {
+            matches!(
+                (open.text.as_str(), token.text.as_str()),
+                ("(", ")") | ("[", "]") | ("{", "}") | ("<", ">")
+            ) && token.kind == Kind::Punct
+        });
+        if closes {
+            let (open, parent) = parents.pop().unwrap();
+            let children = std::mem::replace(&mut nodes, parent);
+            nodes.push(Node::Group(Group {
+                open,
+                children,
+                close: Some(token),
+            }));
+        } else {
+            nodes.push(Node::Token(token));
+        }
+    }
+    while let Some((open, parent)) = parents.pop() {
+        let children = std::mem::replace(&mut nodes, parent);
+        nodes.push(Node::Group(Group {
+            open,
+            children,
+            close: None,
+        }));
+    }
+    nodes
+}
+
+struct Writer<'l> {
+    layout: &'l Layout,
+    /// Whether groups are kept on one line regardless of their width.
+    flat: bool,
+    out: String,
+    column: usize,
+    depth: usize,
+    prev: Option<Token>,
+    /// Width of what has to follow the nodes being written on their line.
+    rest: usize,
+}
+
+impl Writer<'_> {
+    fn newline(&mut self) {
+        if self.column == 0 {
+            return;
+        }
+        let len = self.out.trim_end_matches(' ').len();
+        self.out.truncate(len);
+        self.out.push('\n');
+        self.column = 0;
+    }
+
+    fn blank_line(&mut self) {
+        self.newline();
+        if !self.out.is_empty() && !self.out.ends_with("\n\n") {
+            self.out.push('\n');
+        }
+    }
+
+    fn write(&mut self, text: &str) {
+        if self.column == 0 {
+            let indent = " ".repeat(self.depth * self.layout.indent_width);
+            self.out.push_str(&indent);
+            self.column = indent.len();
+        }
+        self.out.push_str(text);
+        self.column += text.chars().count();
+    }
+
+    fn token(&mut self, token: &Token) {
+        match token.kind {
+            Kind::Comment => {
+                self.newline();
+                for (index, line) in token.text.lines().enumerate() {
+                    let line = line.trim();
+                    if index == 0 || !line.starts_with('*') {
+                        self.write(line);
+                    } else {
+                        self.write(&format!(" {}", line));
+                    }
+                    self.newline();
+                }
+            }
+            Kind::LineComment => {
+                if self.prev.is_some() && self.column != 0 {
+                    self.write(" ");
+                }
+                self.write(token.text.trim_end());
+                self.newline();
+            }
+            Kind::Str => {
+                self.space(token);
+                self.write(&requote(&token.text, self.layout.quotes));
+            }
+            _ => {
+                self.space(token);
+                self.write(&token.text);
+            }
+        }
+        self.prev = Some(token.clone());
+    }
+
+    fn space(&mut self, next: &Token) {
+        if self.column != 0
+            && self
+                .prev
+                .as_ref()
+                .is_some_and(|prev| space_between(prev, next))
+        {
+            self.write(" ");
+        }
+    }
+
+    /// Writes `nodes`, starting a new line where `separator` says, except at
+    /// `skip`.
+    fn nodes(&mut self, nodes: &[Node], separator: Separator, skip: Option<usize>) {
+        for (index, node) in nodes.iter().enumerate() {
+            if Some(index) == skip {
+                continue;
+            }
+            let first = node.first_token();
+            if separator == Separator::Statement && first.blank_line_before && index != 0 {
+                self.blank_line();
+            }
+            if separator == Separator::Operator
+                && index != 0
+                && first.is_any(&LEADING_OPERATORS)
+                && matches!(node, Node::Token(_))
+            {
+                self.newline();
+            }
+            match node {
+                Node::Token(token) => self.token(token),
+                Node::Group(group) => {
+                    let (width, breaks) = head_width(
+                        &nodes[index + 1..],
+                        &mut group.close.as_ref(),
+                        separator == Separator::Operator,
+                    );
+                    let rest = if breaks { width } else { width + self.rest };
+                    let outer = std::mem::replace(&mut self.rest, rest);
+                    self.group(group);
+                    self.rest = outer;
+                }
+            }
+            let ends_line = match (separator, node) {
+                (Separator::Statement, Node::Token(token)) => token.is(";"),
+                (Separator::Statement, Node::Group(_)) => ends_block(nodes, index),
+                (Separator::List, Node::Token(token)) => token.is(","),
+                _ => false,
+            };
+            if ends_line && !self.flat {
+                self.newline();
+            }
+        }
+    }
+
+    fn group(&mut self, group: &Group) {
+        let is_call = self
+            .prev
+            .as_ref()
+            .is_some_and(|prev| prev.is_wordlike() || prev.is_any(&[")", ">"]));
+        self.token(&group.open);
+        let children = &group.children;
+        let separator = Separator::of(children);
+        let is_block = group.open.is("{");
+        let broken = children.iter().any(Node::is_comment)
+            || (is_block && separator == Separator::Statement)
+            || (!self.flat && group.can_break() && !self.fits(group));
+        // Lists broken over several lines end with a comma if allowed, and
+        // lists on one line never do.
+        let last = children.iter().rposition(|node| !node.is_comment());
+        let has_trailing_comma = last.is_some_and(|last| children[last].is(","));
+        let can_trail = separator == Separator::List && (group.open.is_any(&["[", "{"]) || is_call)
+            || (is_block
+                && separator == Separator::None
+                && children.iter().any(|node| node.is(":") || node.is("...")));
+        let wants_trailing_comma =
+            broken && can_trail && self.layout.trailing_commas == TrailingCommas::All;
+        let skip = last.filter(|_| has_trailing_comma && !wants_trailing_comma);
+        let outer = self.rest;
+        if broken {
+            self.rest = 0;
+            self.depth += 1;
+            self.newline();
+            self.nodes(children, separator, skip);
+            if wants_trailing_comma && !has_trailing_comma {
+                self.write(",");
+            }
+            self.depth -= 1;
+            self.newline();
+        } else {
+            self.rest += group.close.as_ref().map_or(0, |close| close.text.len());
+            self.nodes(children, Separator::None, skip);
+        }
+        self.rest = outer;
+        if let Some(close) = &group.close {
+            self.token(close);
+        }
+    }
+
+    /// Whether the group fits in the rest of the line, along with what has
+    /// to follow it.
+    fn fits(&self, group: &Group) -> bool {
+        let mut writer = Writer {
+            layout: self.layout,
+            flat: true,
+            out: String::new(),
+            column: self.column,
+            depth: self.depth,
+            prev: self.prev.clone(),
+            rest: 0,
+        };
+        writer.group(group);
+        !writer.out.contains('\n') && writer.column + self.rest <= self.layout.line_width
+    }
+}
+
+/// Width of `nodes` up to the first place a line can be broken at, and
+/// whether there is one. Groups with a single element never break.
+fn head_width<'n>(
+    nodes: &'n [Node],
+    prev: &mut Option<&'n Token>,
+    breaks_before_operators: bool,
+) -> (usize, bool) {
+    let mut width = 0;
+    for node in nodes {
+        let first = node.first_token();
+        if node.is_comment() || (breaks_before_operators && first.is_any(&LEADING_OPERATORS)) {
+            return (width, true);
+        }
+        width += usize::from(prev.is_some_and(|prev| space_between(prev, first)))
+            + first.text.chars().count();
+        *prev = Some(first);
+        match node {
+            Node::Token(token) if token.is_any(&[",", ";"]) => return (width, true),
+            Node::Token(_) => {}
+            Node::Group(group) => {
+                if group.can_break() {
+                    return (width, true);
+                }
+                let (inner, breaks) = head_width(&group.children, prev, false);
+                width += inner;
+                if breaks {
+                    return (width, true);
+                }
+                if let Some(close) = &group.close {
+                    width += usize::from(prev.is_some_and(|prev| space_between(prev, close)))
+                        + close.text.chars().count();
+                    *prev = Some(close);
+                }
+            }
+        }
+    }
+    (width, false)
+}
+
+/// Whether the node at `index` is a block, such as the body of a function,
+/// that ends a statement. An object followed by `as` (e.g. `as const`) does
+/// not.
+fn ends_block(nodes: &[Node], index: usize) -> bool {
+    matches!(&nodes[index], Node::Group(group) if group.open.is("{"))
+        && nodes.get(index + 1).is_some_and(|next| {
+            let token = next.first_token();
+            token.kind == Kind::Word && token.text != "as"
+        })
+}
+
+fn space_between(prev: &Token, next: &Token) -> bool {
+    if prev.is("{") {
+        return !next.is("}");
+    }
+    if next.is("}") {
+        return true;
+    }
+    if next.is_any(&[",", ";", ")", "]", ".", ":"]) || prev.is_any(&["(", "[", ".", "...", "!"]) {
+        return false;
+    }
+    if prev.is_any(&[",", ":", ";"]) {
+        return !next.is(">");
+    }
+    if prev.is_any(&BINARY_OPERATORS) || next.is_any(&BINARY_OPERATORS) {
+        return true;
+    }
+    (prev.is_wordlike() && next.is_wordlike()) || next.space_before
+}
+
+/// Changes the quotes of a string literal.
+fn requote(text: &str, quotes: Quotes) -> String {
+    let quote = match quotes {
+        Quotes::Double => '"',
+        Quotes::Single => '\'',
+    };
+    let old = text.chars().next().unwrap();
+    if old == quote || text.len() < 2 || !text.ends_with(old) {
+        return text.to_string();
+    }
+    let mut literal = String::from(quote);
+    let mut chars = text[1..text.len() - 1].chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => match chars.next() {
+                Some(ch) if ch == old => literal.push(ch),
+                Some(ch) => {
+                    literal.push('\\');
+                    literal.push(ch);
+                }
+                None => literal.push('\\'),
+            },
+            ch if ch == quote => {
+                literal.push('\\');
+                literal.push(ch);
+            }
+            ch => literal.push(ch),
+        }
+    }
+    literal.push(quote);
+    literal
+}
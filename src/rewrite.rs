@@ -0,0 +1,106 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! In-place rewriting of the AST, shared by the passes that transform CDDL
+//! before it is handed to an engine.
+
+use cddl::ast::{
+    GenericArgs, Group, GroupEntry, MemberKey, NonMemberKey, Rule, Type, Type1, Type2,
+};
+
+/// In-place traversal of the parts of the AST that can contain references.
+pub(crate) trait Rewriter<'a> {
+    fn rewrite_rule(&mut self, rule: &mut Rule<'a>) {
+        match rule {
+            Rule::Type { rule, .. } => self.rewrite_type(&mut rule.value),
+            Rule::Group { rule, .. } => self.rewrite_group_entry(&mut rule.entry),
+        }
+    }
+
+    fn rewrite_type(&mut self, t: &mut Type<'a>) {
+        for choice in &mut t.type_choices {
+            self.rewrite_type1(&mut choice.type1);
+        }
+    }
+
+    fn rewrite_type1(&mut self, t1: &mut Type1<'a>) {
+        self.rewrite_type2(&mut t1.type2);
+        if let Some(operator) = &mut t1.operator {
+            self.rewrite_type2(&mut operator.type2);
+        }
+    }
+
+    fn rewrite_type2(&mut self, t2: &mut Type2<'a>) {
+        walk_type2(self, t2);
+    }
+
+    fn rewrite_group(&mut self, group: &mut Group<'a>) {
+        for choice in &mut group.group_choices {
+            for (entry, _) in &mut choice.group_entries {
+                self.rewrite_group_entry(entry);
+            }
+        }
+    }
+
+    fn rewrite_group_entry(&mut self, entry: &mut GroupEntry<'a>) {
+        walk_group_entry(self, entry);
+    }
+
+    fn rewrite_generic_args(&mut self, args: &mut Option<GenericArgs<'a>>) {
+        for arg in args.iter_mut().flat_map(|args| &mut args.args) {
+            self.rewrite_type1(&mut arg.arg);
+        }
+    }
+}
+
+pub(crate) fn walk_type2<'a, R: Rewriter<'a> + ?Sized>(rewriter: &mut R, t2: &mut Type2<'a>) {
+    match t2 {
+        Type2::Typename { generic_args, .. }
+        | Type2::Unwrap { generic_args, .. }
+        | Type2::ChoiceFromGroup { generic_args, .. } => {
+            rewriter.rewrite_generic_args(generic_args)
+        }
+        Type2::ParenthesizedType { pt, .. } => rewriter.rewrite_type(pt),
+        Type2::TaggedData { t, .. } => rewriter.rewrite_type(t),
+        Type2::Map { group, .. }
+        | Type2::Array { group, .. }
+        | Type2::ChoiceFromInlineGroup { group, .. } => rewriter.rewrite_group(group),
+        _ => {}
+    }
+}
+
+pub(crate) fn walk_group_entry<'a, R: Rewriter<'a> + ?Sized>(
+    rewriter: &mut R,
+    entry: &mut GroupEntry<'a>,
+) {
+    match entry {
+        GroupEntry::ValueMemberKey { ge, .. } => {
+            match &mut ge.member_key {
+                Some(MemberKey::Type1 { t1, .. }) => rewriter.rewrite_type1(t1),
+                Some(MemberKey::NonMemberKey {
+                    non_member_key: NonMemberKey::Group(group),
+                    ..
+                }) => rewriter.rewrite_group(group),
+                Some(MemberKey::NonMemberKey {
+                    non_member_key: NonMemberKey::Type(t),
+                    ..
+                }) => rewriter.rewrite_type(t),
+                _ => {}
+            }
+            rewriter.rewrite_type(&mut ge.entry_type);
+        }
+        GroupEntry::TypeGroupname { ge, .. } => rewriter.rewrite_generic_args(&mut ge.generic_args),
+        GroupEntry::InlineGroup { group, .. } => rewriter.rewrite_group(group),
+    }
+}
@@ -0,0 +1,543 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Resolution of the unwrap (`~rule`) and choice-from-group (`&group`)
+//! operators into what they stand for.
+//!
+//! For example, `[~header, payload: bytes]` with `header = [id: uint]` becomes
+//! `[id: uint, payload: bytes]`, and `&colors` with `colors = (red: 1, blue: 2)`
+//! becomes `(1 / 2)`.
+
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap, HashSet},
+};
+
+use cddl::{
+    ast::{
+        GenericArgs, Group, GroupEntry, Identifier, MemberKey, OptionalComma, Rule, Type, Type1,
+        Type2, TypeChoice, ValueMemberKeyEntry, CDDL,
+    },
+    token::Value,
+};
+
+use crate::{
+    monomorphize::instantiate,
+    rewrite::{walk_type2, Rewriter},
+    util::{literal_value, unescape_text, LiteralMembers},
+};
+
+/// Returns a copy of `cddl` in which unwrapped maps and arrays are inlined
+/// into the groups they are used in, unwrapped tags are replaced by their
+/// content and choices from groups are replaced by the choice of the values
+/// of the members.
+///
+/// Generic rules are unwrapped with their arguments substituted. Unwraps that
+/// cannot be resolved (e.g. of amended, unknown or recursive rules) are
+/// reported. In types they become references to the rule, and in groups they
+/// are left out.
+pub fn unwrap<'a>(cddl: &CDDL<'a>) -> (CDDL<'a>, Vec<String>) {
+    let mut unwrapping = Unwrapping::new(cddl);
+    let mut result = cddl.clone();
+    for rule in &mut result.rules {
+        unwrapping.rewrite_rule(rule);
+    }
+    (result, unwrapping.diagnostics)
+}
+
+/// Members of the rules that are a choice from a group of literal values
+/// (e.g. `color = &colors`), keyed by rule name, as `(name, value)` pairs in
+/// declaration order. The values are JavaScript literals.
+pub fn choice_enums(cddl: &CDDL) -> BTreeMap<String, LiteralMembers> {
+    let mut unwrapping = Unwrapping::new(cddl);
+    let mut enums = BTreeMap::new();
+    for rule in &cddl.rules {
+        let Rule::Type { rule, .. } = rule else {
+            continue;
+        };
+        if rule.generic_params.is_some() || rule.name.socket.is_some() {
+            continue;
+        }
+        let [TypeChoice {
+            type1:
+                Type1 {
+                    type2,
+                    operator: None,
+                    ..
+                },
+            ..
+        }] = rule.value.type_choices.as_slice()
+        else {
+            continue;
+        };
+        let members = match type2 {
+            Type2::ChoiceFromGroup {
+                ident,
+                generic_args,
+                ..
+            } => unwrapping.group_rule_members(ident.ident, generic_args.as_ref()),
+            Type2::ChoiceFromInlineGroup { group, .. } => unwrapping.members(group),
+            _ => None,
+        };
+        let literals = members.and_then(|members| {
+            members
+                .iter()
+                .map(|member| {
+                    let name = match &member.member_key {
+                        Some(MemberKey::Bareword { ident, .. }) => ident.ident.to_string(),
+                        Some(MemberKey::Value {
+                            value: Value::TEXT(value),
+                            ..
+                        }) => unescape_text(value),
+                        _ => return None,
+                    };
+                    Some((name, literal_value(&member.entry_type)?))
+                })
+                .collect::<Option<Vec<_>>>()
+        });
+        if let Some(literals) = literals {
+            enums.insert(rule.name.ident.to_string(), literals);
+        }
+    }
+    enums
+}
+
+struct Unwrapping<'r, 'a> {
+    /// Rules defined exactly once, by name.
+    rules: HashMap<&'a str, &'r Rule<'a>>,
+    /// Names of the group rules.
+    groups: HashSet<&'a str>,
+    /// The rule being rewritten.
+    rule: Option<&'a str>,
+    /// Rules being resolved, to stop at recursive ones.
+    visiting: HashSet<&'a str>,
+    /// Unwraps that could not be resolved.
+    diagnostics: Vec<String>,
+}
+
+impl<'r, 'a> Unwrapping<'r, 'a> {
+    fn new(cddl: &'r CDDL<'a>) -> Self {
+        let mut definitions = HashMap::<_, Vec<_>>::new();
+        let mut groups = HashSet::new();
+        for rule in &cddl.rules {
+            let name = match rule {
+                Rule::Type { rule, .. } => rule.name.ident,
+                Rule::Group { rule, .. } => {
+                    groups.insert(rule.name.ident);
+                    rule.name.ident
+                }
+            };
+            definitions.entry(name).or_default().push(rule);
+        }
+        Unwrapping {
+            rules: definitions
+                .into_iter()
+                .filter_map(|(name, rules)| match rules.as_slice() {
+                    [rule] => Some((name, *rule)),
+                    _ => None,
+                })
+                .collect(),
+            groups,
+            rule: None,
+            visiting: HashSet::new(),
+            diagnostics: Vec::new(),
+        }
+    }
+
+    /// The rule `name` refers to with `args`, with the arguments substituted
+    /// for the parameters of generic rules.
+    fn rule(&mut self, name: &str, args: Option<&GenericArgs<'a>>) -> Option<Cow<'r, Rule<'a>>> {
+        let rule = self.rules.get(name).copied()?;
+        let params = match rule {
+            Rule::Type { rule, .. } => rule.generic_params.as_ref(),
+            Rule::Group { rule, .. } => rule.generic_params.as_ref(),
+        };
+        match (params, args) {
+            (None, None) => Some(Cow::Borrowed(rule)),
+            (Some(params), Some(args)) => {
+                let rule = instantiate(rule, args);
+                if rule.is_none() {
+                    self.report(format!(
+                        "`{}` expects {} generic arguments, got {}.",
+                        name,
+                        params.params.len(),
+                        args.args.len()
+                    ));
+                }
+                rule.map(Cow::Owned)
+            }
+            _ => None,
+        }
+    }
+
+    fn report(&mut self, diagnostic: String) {
+        if !self.diagnostics.contains(&diagnostic) {
+            self.diagnostics.push(diagnostic);
+        }
+    }
+
+    /// The members of a group, including those of the groups it references.
+    fn members(&mut self, group: &Group<'a>) -> Option<Vec<ValueMemberKeyEntry<'a>>> {
+        let mut members = Vec::new();
+        for choice in &group.group_choices {
+            for (entry, _) in &choice.group_entries {
+                members.extend(self.entry_members(entry)?);
+            }
+        }
+        Some(members)
+    }
+
+    fn entry_members(&mut self, entry: &GroupEntry<'a>) -> Option<Vec<ValueMemberKeyEntry<'a>>> {
+        match entry {
+            GroupEntry::ValueMemberKey { ge, .. } if ge.member_key.is_some() => {
+                Some(vec![(**ge).clone()])
+            }
+            // Bare group names may parse as members without a key. Other
+            // members without a key only have a value.
+            GroupEntry::ValueMemberKey { ge, .. } => match ge.entry_type.type_choices.as_slice() {
+                [TypeChoice {
+                    type1:
+                        Type1 {
+                            type2:
+                                Type2::Typename {
+                                    ident,
+                                    generic_args,
+                                    ..
+                                },
+                            operator: None,
+                            ..
+                        },
+                    ..
+                }] if self.groups.contains(ident.ident) => {
+                    self.group_rule_members(ident.ident, generic_args.as_ref())
+                }
+                _ => Some(vec![(**ge).clone()]),
+            },
+            GroupEntry::TypeGroupname { ge, .. } if self.groups.contains(ge.name.ident) => {
+                self.group_rule_members(ge.name.ident, ge.generic_args.as_ref())
+            }
+            GroupEntry::TypeGroupname { ge, span, .. } => Some(vec![ValueMemberKeyEntry {
+                occur: ge.occur.clone(),
+                member_key: None,
+                entry_type: Type {
+                    type_choices: vec![TypeChoice {
+                        type1: Type1 {
+                            type2: Type2::Typename {
+                                ident: ge.name.clone(),
+                                generic_args: ge.generic_args.clone(),
+                                span: *span,
+                            },
+                            operator: None,
+                            span: *span,
+                            comments_after_type: None,
+                        },
+                        comments_before_type: None,
+                        comments_after_type: None,
+                    }],
+                    span: *span,
+                },
+            }]),
+            GroupEntry::InlineGroup { group, .. } => self.members(group),
+        }
+    }
+
+    fn group_rule_members(
+        &mut self,
+        name: &'a str,
+        args: Option<&GenericArgs<'a>>,
+    ) -> Option<Vec<ValueMemberKeyEntry<'a>>> {
+        let rule = self.rule(name, args)?;
+        let Rule::Group { rule, .. } = rule.as_ref() else {
+            return None;
+        };
+        if !self.visiting.insert(name) {
+            return None;
+        }
+        let members = self.entry_members(&rule.entry);
+        self.visiting.remove(name);
+        members
+    }
+
+    /// The entries of the maps or arrays `name` is defined as, themselves
+    /// unwrapped. Several group choices become a single inline group of them.
+    fn unwrapped_entries(
+        &mut self,
+        name: &'a str,
+        args: Option<&GenericArgs<'a>>,
+    ) -> Option<Vec<(GroupEntry<'a>, OptionalComma<'a>)>> {
+        if self.rule == Some(name) || self.visiting.contains(name) {
+            self.report(format!(
+                "Cannot unwrap `~{}` into a group, as it contains itself; it is left out.",
+                name
+            ));
+            return None;
+        }
+        let rule = self.rule(name, args)?;
+        let Rule::Type { rule, .. } = rule.as_ref() else {
+            return None;
+        };
+        let mut is_map = None;
+        let mut choices = Vec::new();
+        for choice in &rule.value.type_choices {
+            let (group, map) = match &choice.type1 {
+                Type1 {
+                    type2: Type2::Map { group, .. },
+                    operator: None,
+                    ..
+                } => (group, true),
+                Type1 {
+                    type2: Type2::Array { group, .. },
+                    operator: None,
+                    ..
+                } => (group, false),
+                _ => return None,
+            };
+            // Maps and arrays cannot be unwrapped into the same group.
+            if *is_map.get_or_insert(map) != map {
+                return None;
+            }
+            choices.extend(group.group_choices.iter().cloned());
+        }
+        self.visiting.insert(name);
+        let mut group = Group {
+            group_choices: choices,
+            span: Default::default(),
+        };
+        self.rewrite_group(&mut group);
+        self.visiting.remove(name);
+        if let [choice] = group.group_choices.as_mut_slice() {
+            return Some(std::mem::take(&mut choice.group_entries));
+        }
+        Some(vec![(
+            GroupEntry::InlineGroup {
+                occur: None,
+                group,
+                span: Default::default(),
+                comments_before_group: None,
+                comments_after_group: None,
+            },
+            OptionalComma {
+                optional_comma: true,
+                trailing_comments: None,
+                _a: Default::default(),
+            },
+        )])
+    }
+
+    /// The type `t2` stands for, if it is an unwrapped tag or a choice from a
+    /// group.
+    fn resolve_type2(&mut self, t2: &Type2<'a>) -> Option<Type<'a>> {
+        match t2 {
+            Type2::Unwrap {
+                ident,
+                generic_args,
+                ..
+            } => match self.rule(ident.ident, generic_args.as_ref()).as_deref() {
+                Some(Rule::Type { rule, .. }) => match rule.value.type_choices.as_slice() {
+                    [TypeChoice {
+                        type1:
+                            Type1 {
+                                type2: Type2::TaggedData { t, .. },
+                                operator: None,
+                                ..
+                            },
+                        ..
+                    }] => Some(t.clone()),
+                    _ => None,
+                },
+                _ => None,
+            },
+            Type2::ChoiceFromGroup {
+                ident,
+                generic_args,
+                ..
+            } => self
+                .group_rule_members(ident.ident, generic_args.as_ref())
+                .map(choice_type),
+            Type2::ChoiceFromInlineGroup { group, .. } => self.members(group).map(choice_type),
+            _ => None,
+        }
+    }
+}
+
+impl<'a> Rewriter<'a> for Unwrapping<'_, 'a> {
+    fn rewrite_rule(&mut self, rule: &mut Rule<'a>) {
+        match rule {
+            Rule::Type { rule, .. } => {
+                self.rule = Some(rule.name.ident);
+                self.rewrite_type(&mut rule.value);
+            }
+            Rule::Group { rule, .. } => {
+                self.rule = Some(rule.name.ident);
+                self.rewrite_group_entry(&mut rule.entry);
+            }
+        }
+        self.rule = None;
+    }
+
+    fn rewrite_type2(&mut self, t2: &mut Type2<'a>) {
+        let name = match t2 {
+            Type2::Unwrap { ident, .. } | Type2::ChoiceFromGroup { ident, .. } => Some(ident.ident),
+            _ => None,
+        };
+        if name.is_some_and(|name| self.visiting.contains(name)) {
+            return walk_type2(self, t2);
+        }
+        let Some(mut pt) = self.resolve_type2(t2) else {
+            if let Type2::Unwrap {
+                ident,
+                generic_args,
+                span,
+                ..
+            } = t2
+            {
+                self.report(format!(
+                    "Cannot unwrap `~{}` into a type; it is referenced as `{}` instead.",
+                    ident.ident, ident.ident
+                ));
+                *t2 = Type2::Typename {
+                    ident: ident.clone(),
+                    generic_args: generic_args.take(),
+                    span: *span,
+                };
+            }
+            return walk_type2(self, t2);
+        };
+        self.visiting.extend(name);
+        self.rewrite_type(&mut pt);
+        if let Some(name) = name {
+            self.visiting.remove(name);
+        }
+        *t2 = Type2::ParenthesizedType {
+            pt,
+            span: Default::default(),
+            comments_before_type: None,
+            comments_after_type: None,
+        };
+    }
+
+    fn rewrite_group(&mut self, group: &mut Group<'a>) {
+        for choice in &mut group.group_choices {
+            let mut entries = Vec::new();
+            let mut iter = std::mem::take(&mut choice.group_entries)
+                .into_iter()
+                .peekable();
+            while let Some((mut entry, comma)) = iter.next() {
+                // The parser repeats the name of a trailing unwrap as another
+                // entry.
+                if let Some(ident) = trailing_unwrap(&entry) {
+                    iter.next_if(|(next, _)| is_reference_to(next, ident));
+                }
+                if let Some((ident, args)) = unwrapped_ident(&entry) {
+                    let reported = self.diagnostics.len();
+                    match self.unwrapped_entries(ident, args) {
+                        Some(unwrapped) => entries.extend(unwrapped),
+                        None if self.diagnostics.len() > reported => {}
+                        None => self.report(format!(
+                            "Cannot unwrap `~{}` into a group, as it is not a map or an array; \
+                            it is left out.",
+                            ident
+                        )),
+                    }
+                    continue;
+                }
+                self.rewrite_group_entry(&mut entry);
+                entries.push((entry, comma));
+            }
+            choice.group_entries = entries;
+        }
+    }
+}
+
+/// The choice of the values of the members.
+fn choice_type(members: Vec<ValueMemberKeyEntry>) -> Type {
+    Type {
+        type_choices: members
+            .into_iter()
+            .flat_map(|member| member.entry_type.type_choices)
+            .collect(),
+        span: Default::default(),
+    }
+}
+
+/// The name of the rule unwrapped by a group entry (e.g. `~header`), and its
+/// generic arguments.
+fn unwrapped_ident<'e, 'a>(
+    entry: &'e GroupEntry<'a>,
+) -> Option<(&'a str, Option<&'e GenericArgs<'a>>)> {
+    let GroupEntry::ValueMemberKey { ge, .. } = entry else {
+        return None;
+    };
+    if ge.member_key.is_some() || ge.occur.is_some() {
+        return None;
+    }
+    match ge.entry_type.type_choices.as_slice() {
+        [TypeChoice {
+            type1:
+                Type1 {
+                    type2:
+                        Type2::Unwrap {
+                            ident,
+                            generic_args,
+                            ..
+                        },
+                    operator: None,
+                    ..
+                },
+            ..
+        }] => Some((ident.ident, generic_args.as_ref())),
+        _ => None,
+    }
+}
+
+/// The name of the rule unwrapped at the end of a group entry (e.g.
+/// `href: ~uri`).
+fn trailing_unwrap<'e, 'a>(entry: &'e GroupEntry<'a>) -> Option<&'e Identifier<'a>> {
+    let GroupEntry::ValueMemberKey { ge, .. } = entry else {
+        return None;
+    };
+    match ge.entry_type.type_choices.last() {
+        Some(TypeChoice {
+            type1:
+                Type1 {
+                    type2: Type2::Unwrap { ident, .. },
+                    operator: None,
+                    ..
+                },
+            ..
+        }) => Some(ident),
+        _ => None,
+    }
+}
+
+fn is_reference_to(entry: &GroupEntry, ident: &Identifier) -> bool {
+    let name = match entry {
+        GroupEntry::ValueMemberKey { ge, .. } if ge.member_key.is_none() => {
+            match ge.entry_type.type_choices.as_slice() {
+                [TypeChoice {
+                    type1:
+                        Type1 {
+                            type2: Type2::Typename { ident, .. },
+                            ..
+                        },
+                    ..
+                }] => ident,
+                _ => return false,
+            }
+        }
+        GroupEntry::TypeGroupname { ge, .. } => &ge.name,
+        _ => return false,
+    };
+    name.ident == ident.ident && name.span == ident.span
+}
@@ -12,8 +12,17 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::{BTreeMap, BTreeSet};
+
 use convert_case::{Case, Casing};
 
+use crate::options::Renames;
+
+const MAX_SAFE_INTEGER: i128 = (1 << 53) - 1;
+
+/// Literal members of a map as `(name, value)` pairs.
+pub type LiteralMembers = Vec<(String, String)>;
+
 fn split_identifier<T: ToString>(value: T) -> Vec<String> {
     return value.to_string().split('.').map(String::from).collect();
 }
@@ -22,6 +31,10 @@ pub fn to_pascalcase<T: ToString>(value: T) -> String {
     value.to_string().to_case(Case::Pascal)
 }
 
+pub fn to_camelcase<T: ToString>(value: T) -> String {
+    value.to_string().to_case(Case::Camel)
+}
+
 pub fn to_namespaced<T: ToString>(value: T) -> String {
     split_identifier(&value.to_string())
         .into_iter()
@@ -39,10 +52,610 @@ pub fn split_namespaced<T: ToString>(value: T) -> (Vec<String>, String) {
     (parts, value)
 }
 
-pub fn is_alphaspace<T: AsRef<str>>(value: T) -> bool {
+/// The value of a CDDL text string from its text between the quotes, in
+/// which `\` escapes the next character or starts an escape sequence like
+/// JSON's.
+pub fn unescape_text<T: AsRef<str>>(text: T) -> String {
+    let text = text.as_ref();
+    let mut value = String::new();
+    let mut chars = text.char_indices();
+    while let Some((_, ch)) = chars.next() {
+        if ch != '\\' {
+            value.push(ch);
+            continue;
+        }
+        match chars.next() {
+            Some((_, 'n')) => value.push('\n'),
+            Some((_, 'r')) => value.push('\r'),
+            Some((_, 't')) => value.push('\t'),
+            Some((_, 'b')) => value.push('\u{8}'),
+            Some((_, 'f')) => value.push('\u{c}'),
+            Some((offset, 'u')) => {
+                let rest = &text[offset + 1..];
+                let (code, len) = match rest.strip_prefix('{') {
+                    Some(braced) => match braced.find('}') {
+                        Some(end) => (u32::from_str_radix(&braced[..end], 16).ok(), end + 2),
+                        None => (None, 0),
+                    },
+                    None => match rest.get(..4) {
+                        Some(hex) => (u32::from_str_radix(hex, 16).ok(), 4),
+                        None => (None, 0),
+                    },
+                };
+                let Some(mut code) = code else {
+                    value.push('u');
+                    continue;
+                };
+                let mut len = len;
+                // A high surrogate is followed by the low one.
+                if (0xd800..0xdc00).contains(&code) {
+                    if let Some(low) = rest[len..]
+                        .strip_prefix("\\u")
+                        .and_then(|low| low.get(..4))
+                        .and_then(|low| u32::from_str_radix(low, 16).ok())
+                        .filter(|low| (0xdc00..0xe000).contains(low))
+                    {
+                        code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00);
+                        len += 6;
+                    }
+                }
+                value.push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+                for _ in rest[..len].chars() {
+                    chars.next();
+                }
+            }
+            Some((_, ch)) => value.push(ch),
+            None => value.push('\\'),
+        }
+    }
     value
-        .as_ref()
-        .to_ascii_lowercase()
-        .bytes()
-        .all(|ch| b'a' <= ch && ch <= b'z' || ch == b' ')
+}
+
+/// The JavaScript string literal of a CDDL text string (see
+/// [`unescape_text`]).
+pub fn text_literal<T: AsRef<str>>(text: T) -> String {
+    to_string_literal(unescape_text(text))
+}
+
+/// A JavaScript string literal with the given value.
+pub fn to_string_literal<T: AsRef<str>>(value: T) -> String {
+    let mut literal = String::from("\"");
+    for ch in value.as_ref().chars() {
+        match ch {
+            '"' => literal.push_str("\\\""),
+            '\\' => literal.push_str("\\\\"),
+            '\n' => literal.push_str("\\n"),
+            '\r' => literal.push_str("\\r"),
+            '\t' => literal.push_str("\\t"),
+            ch if ch.is_control() => literal.push_str(&format!("\\u{{{:x}}}", ch as u32)),
+            ch => literal.push(ch),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+/// The name of a member with a bareword or value key, as it is serialized.
+pub fn member_key_name(mk: &cddl::ast::MemberKey) -> Option<String> {
+    match mk {
+        cddl::ast::MemberKey::Bareword { ident, .. } => Some(ident.ident.to_string()),
+        cddl::ast::MemberKey::Value { value, .. } => Some(match value {
+            cddl::token::Value::INT(value) => value.to_string(),
+            cddl::token::Value::UINT(value) => value.to_string(),
+            cddl::token::Value::FLOAT(value) => value.to_string(),
+            cddl::token::Value::TEXT(value) => unescape_text(value),
+            cddl::token::Value::BYTE(value) => value.to_string(),
+        }),
+        _ => None,
+    }
+}
+
+/// The bytes of a byte string value, of which `h''` and `b64''` give the
+/// base16 and base64url encoding. `None` if the encoding is not valid.
+pub fn byte_value(value: &cddl::token::ByteValue) -> Option<Vec<u8>> {
+    let encoded = |value: &[u8]| {
+        value
+            .iter()
+            .filter(|byte| !byte.is_ascii_whitespace())
+            .copied()
+            .collect::<Vec<_>>()
+    };
+    match value {
+        cddl::token::ByteValue::UTF8(value) => Some(value.to_vec()),
+        cddl::token::ByteValue::B16(value) => data_encoding::HEXLOWER_PERMISSIVE
+            .decode(&encoded(value))
+            .ok(),
+        cddl::token::ByteValue::B64(value) => {
+            // Padding and the characters of base64 are accepted as well.
+            let mut value = encoded(value)
+                .into_iter()
+                .map(|byte| match byte {
+                    b'+' => b'-',
+                    b'/' => b'_',
+                    byte => byte,
+                })
+                .collect::<Vec<_>>();
+            while value.last() == Some(&b'=') {
+                value.pop();
+            }
+            data_encoding::BASE64URL_NOPAD.decode(&value).ok()
+        }
+    }
+}
+
+/// The text of `bytes` in JSON, i.e. base64url without padding as RFC 8949
+/// converts byte strings.
+pub fn to_base64url(bytes: &[u8]) -> String {
+    data_encoding::BASE64URL_NOPAD.encode(bytes)
+}
+
+/// Whether a CDDL text string can name an enum member, i.e. has letters to
+/// name it after (e.g. `"no-cors"`, but not `"*"`).
+pub fn is_enum_value<T: AsRef<str>>(text: T) -> bool {
+    unescape_text(text).chars().any(char::is_alphabetic)
+}
+
+/// Whether the rule is a non-generic alias of a primitive type, possibly
+/// constrained by an operator (e.g. `id = text` or `index = 0..10`).
+pub fn is_primitive_alias(tr: &cddl::ast::TypeRule) -> bool {
+    if tr.generic_params.is_some() || tr.value.type_choices.len() != 1 {
+        return false;
+    }
+    let type1 = &tr.value.type_choices[0].type1;
+    match &type1.type2 {
+        cddl::ast::Type2::Typename {
+            ident,
+            generic_args: None,
+            ..
+        } => matches!(
+            ident.ident,
+            "bool"
+                | "uint"
+                | "nint"
+                | "int"
+                | "float16"
+                | "float32"
+                | "float64"
+                | "float16-32"
+                | "float32-64"
+                | "float"
+                | "number"
+                | "biguint"
+                | "bignint"
+                | "bigint"
+                | "bstr"
+                | "bytes"
+                | "tstr"
+                | "text"
+        ),
+        cddl::ast::Type2::IntValue { .. }
+        | cddl::ast::Type2::UintValue { .. }
+        | cddl::ast::Type2::FloatValue { .. } => matches!(
+            type1.operator,
+            Some(cddl::ast::Operator {
+                operator: cddl::ast::RangeCtlOp::RangeOp { .. },
+                ..
+            })
+        ),
+        _ => false,
+    }
+}
+
+/// Whether the bounds of a range can be represented exactly by a JavaScript
+/// `number`.
+pub fn is_safe_range(t1: &cddl::ast::Type1) -> bool {
+    let is_safe = |type2: &cddl::ast::Type2| match type2 {
+        cddl::ast::Type2::IntValue { value, .. } => (*value as i128).abs() <= MAX_SAFE_INTEGER,
+        cddl::ast::Type2::UintValue { value, .. } => (*value as i128) <= MAX_SAFE_INTEGER,
+        _ => true,
+    };
+    match &t1.operator {
+        Some(cddl::ast::Operator {
+            operator: cddl::ast::RangeCtlOp::RangeOp { .. },
+            type2,
+            ..
+        }) => is_safe(&t1.type2) && is_safe(type2),
+        _ => true,
+    }
+}
+
+/// The value type of a catch-all member (e.g. `* tstr => any`), which allows
+/// any additional members in a map.
+pub fn catch_all_type<'b, 'a>(
+    entry: &'b cddl::ast::GroupEntry<'a>,
+) -> Option<&'b cddl::ast::Type<'a>> {
+    let cddl::ast::GroupEntry::ValueMemberKey { ge, .. } = entry else {
+        return None;
+    };
+    let Some(cddl::ast::MemberKey::Type1 { t1, .. }) = &ge.member_key else {
+        return None;
+    };
+    let is_text = matches!(
+        &t1.type2,
+        cddl::ast::Type2::Typename {
+            ident,
+            generic_args: None,
+            ..
+        } if ident.socket.is_none() && matches!(ident.ident, "tstr" | "text")
+    );
+    let is_any_number = matches!(
+        &ge.occur,
+        Some(cddl::ast::Occurrence {
+            occur: cddl::ast::Occur::ZeroOrMore { .. }
+                | cddl::ast::Occur::Exact {
+                    lower: None | Some(0),
+                    upper: None,
+                    ..
+                },
+            ..
+        })
+    );
+    (is_text && t1.operator.is_none() && is_any_number).then_some(&ge.entry_type)
+}
+
+/// Like [`catch_all_type`], but for group rules made of a single catch-all
+/// member (e.g. `Extensible = (* text => any)`).
+pub fn catch_all_group_type<'b, 'a>(
+    gr: &'b cddl::ast::GroupRule<'a>,
+) -> Option<&'b cddl::ast::Type<'a>> {
+    if gr.generic_params.is_some() || gr.name.socket.is_some() {
+        return None;
+    }
+    match &gr.entry {
+        cddl::ast::GroupEntry::InlineGroup {
+            occur: None, group, ..
+        } => match group.group_choices.as_slice() {
+            [choice] => match choice.group_entries.as_slice() {
+                [(entry, _)] => catch_all_type(entry),
+                _ => None,
+            },
+            _ => None,
+        },
+        entry => catch_all_type(entry),
+    }
+}
+
+/// Literal-valued required members (e.g. `type: "success"`) of a map made of a
+/// single choice of plain members, as `(name, value)` pairs in declaration
+/// order. The values are JavaScript literals.
+///
+/// References to groups are resolved with `groups`. Returns `None` for maps
+/// composed from other groups or with group choices.
+pub fn literal_members(
+    group: &cddl::ast::Group,
+    groups: &dyn Fn(&str) -> Option<LiteralMembers>,
+) -> Option<LiteralMembers> {
+    let [choice] = group.group_choices.as_slice() else {
+        return None;
+    };
+    entry_literal_members(choice.group_entries.iter().map(|(entry, _)| entry), groups)
+}
+
+/// Like [`literal_members`], but for the entries of a single group choice.
+pub fn entry_literal_members<'e, 'a: 'e>(
+    entries: impl IntoIterator<Item = &'e cddl::ast::GroupEntry<'a>>,
+    groups: &dyn Fn(&str) -> Option<LiteralMembers>,
+) -> Option<LiteralMembers> {
+    let mut members = Vec::new();
+    for entry in entries {
+        let ge = match entry {
+            cddl::ast::GroupEntry::ValueMemberKey { ge, .. } => ge,
+            cddl::ast::GroupEntry::TypeGroupname { ge, .. }
+                if ge.occur.is_none() && ge.generic_args.is_none() && ge.name.socket.is_none() =>
+            {
+                members.extend(groups(ge.name.ident)?);
+                continue;
+            }
+            _ => return None,
+        };
+        let key = match &ge.member_key {
+            Some(cddl::ast::MemberKey::Bareword { ident, .. }) => ident.ident.to_string(),
+            Some(cddl::ast::MemberKey::Value {
+                value: cddl::token::Value::TEXT(value),
+                ..
+            }) => unescape_text(value),
+            _ => return None,
+        };
+        if ge.occur.is_some() {
+            continue;
+        }
+        let Some(value) = literal_value(&ge.entry_type) else {
+            continue;
+        };
+        members.push((key, value));
+    }
+    Some(members)
+}
+
+/// The JavaScript literal of a type that is a single literal value (e.g.
+/// `"success"`, `1` or `true`).
+pub fn literal_value(t: &cddl::ast::Type) -> Option<String> {
+    let [choice] = t.type_choices.as_slice() else {
+        return None;
+    };
+    if choice.type1.operator.is_some() {
+        return None;
+    }
+    match &choice.type1.type2 {
+        cddl::ast::Type2::TextValue { value, .. } => Some(text_literal(value)),
+        cddl::ast::Type2::UintValue { value, .. } => Some(value.to_string()),
+        cddl::ast::Type2::IntValue { value, .. } => Some(value.to_string()),
+        cddl::ast::Type2::FloatValue { value, .. } => Some(value.to_string()),
+        cddl::ast::Type2::Typename {
+            ident,
+            generic_args: None,
+            ..
+        } if matches!(ident.ident, "true" | "false" | "null") => Some(ident.ident.to_string()),
+        _ => None,
+    }
+}
+
+/// Finds a member whose literal value is present in every variant and tells
+/// them apart.
+pub fn find_discriminator(variants: &[LiteralMembers]) -> Option<&str> {
+    let (first, rest) = variants.split_first()?;
+    first.iter().find_map(|(key, value)| {
+        let mut values = BTreeSet::from([value]);
+        rest.iter()
+            .all(|variant| {
+                variant
+                    .iter()
+                    .find(|(other, _)| other == key)
+                    .is_some_and(|(_, value)| values.insert(value))
+            })
+            .then_some(key.as_str())
+    })
+}
+
+/// The literal members that every choice of `group` has, if each choice is
+/// made of members and plain groups.
+pub fn common_literal_members(
+    group: &cddl::ast::Group,
+    groups: &dyn Fn(&str) -> Option<LiteralMembers>,
+) -> Option<LiteralMembers> {
+    let mut choices = group.group_choices.iter().map(|choice| {
+        entry_literal_members(choice.group_entries.iter().map(|(entry, _)| entry), groups)
+    });
+    let mut common = choices.next()??;
+    for members in choices {
+        let members = members?;
+        common.retain(|member| members.contains(member));
+    }
+    Some(common)
+}
+
+/// Group rules made of several choices of members and plain groups, with the
+/// literal members that every choice has. The literal members of other groups
+/// are given by `groups`. Recursive groups never are.
+pub fn choice_groups(
+    cddl: &cddl::ast::CDDL,
+    renames: &Renames,
+    groups: &dyn Fn(&str) -> Option<LiteralMembers>,
+) -> BTreeMap<String, LiteralMembers> {
+    let mut candidates = BTreeMap::new();
+    for rule in &cddl.rules {
+        let cddl::ast::Rule::Group { rule, .. } = rule else {
+            continue;
+        };
+        if rule.generic_params.is_some()
+            || rule.name.socket.is_some()
+            || renames.properties.contains_key(rule.name.ident)
+        {
+            continue;
+        }
+        if let cddl::ast::GroupEntry::InlineGroup {
+            occur: None, group, ..
+        } = &rule.entry
+        {
+            if group.group_choices.len() > 1 {
+                candidates.insert(rule.name.ident, group);
+            }
+        }
+    }
+    let mut choice_groups = BTreeMap::new();
+    loop {
+        let mut changed = false;
+        for (name, group) in &candidates {
+            if choice_groups.contains_key(*name) {
+                continue;
+            }
+            let groups = |name: &str| groups(name).or_else(|| choice_groups.get(name).cloned());
+            if let Some(literals) = common_literal_members(group, &groups) {
+                choice_groups.insert(name.to_string(), literals);
+                changed = true;
+            }
+        }
+        if !changed {
+            return choice_groups;
+        }
+    }
+}
+
+/// Group rules made of a single choice of members and plain groups, with their
+/// literal members. Recursive groups never are.
+///
+/// Renamed members are moved by a transform, which neither discriminated
+/// unions nor spreads see through, so groups with renames are skipped.
+pub fn plain_groups(cddl: &cddl::ast::CDDL, renames: &Renames) -> BTreeMap<String, LiteralMembers> {
+    let mut candidates = BTreeMap::new();
+    for rule in &cddl.rules {
+        let cddl::ast::Rule::Group { rule, .. } = rule else {
+            continue;
+        };
+        if rule.generic_params.is_some()
+            || rule.name.socket.is_some()
+            || renames.properties.contains_key(rule.name.ident)
+        {
+            continue;
+        }
+        candidates.insert(rule.name.ident, &rule.entry);
+    }
+    let mut plain_groups = BTreeMap::new();
+    loop {
+        let mut changed = false;
+        for (name, entry) in &candidates {
+            if plain_groups.contains_key(*name) {
+                continue;
+            }
+            let groups = |name: &str| plain_groups.get(name).cloned();
+            let literals = match entry {
+                cddl::ast::GroupEntry::InlineGroup {
+                    occur: None, group, ..
+                } => literal_members(group, &groups),
+                cddl::ast::GroupEntry::ValueMemberKey { .. } => {
+                    entry_literal_members([*entry], &groups)
+                }
+                _ => None,
+            };
+            if let Some(literals) = literals {
+                plain_groups.insert(name.to_string(), literals);
+                changed = true;
+            }
+        }
+        if !changed {
+            return plain_groups;
+        }
+    }
+}
+
+/// Type rules that are plain maps, with their literal members. References to
+/// groups are resolved with `groups`.
+pub fn map_literals(
+    cddl: &cddl::ast::CDDL,
+    renames: &Renames,
+    groups: &dyn Fn(&str) -> Option<LiteralMembers>,
+) -> BTreeMap<String, LiteralMembers> {
+    let mut map_literals = BTreeMap::new();
+    for rule in &cddl.rules {
+        let cddl::ast::Rule::Type { rule, .. } = rule else {
+            continue;
+        };
+        if rule.generic_params.is_some()
+            || rule.name.socket.is_some()
+            || renames.properties.contains_key(rule.name.ident)
+        {
+            continue;
+        }
+        if let [cddl::ast::TypeChoice {
+            type1:
+                cddl::ast::Type1 {
+                    type2: cddl::ast::Type2::Map { group, .. },
+                    operator: None,
+                    ..
+                },
+            ..
+        }] = rule.value.type_choices.as_slice()
+        {
+            if let Some(literals) = literal_members(group, groups) {
+                map_literals.insert(rule.name.ident.to_string(), literals);
+            }
+        }
+    }
+    map_literals
+}
+
+/// Well-known CBOR tags as `(tag, prelude type, content types)`. The prelude
+/// type is the tag's natural representation. The content types are the ones
+/// the tag is defined over.
+const WELL_KNOWN_TAGS: &[(u64, &str, &[&str])] = &[
+    (0, "tdate", &["tstr", "text"]),
+    (1, "time", &["number"]),
+    (2, "biguint", &["bstr", "bytes"]),
+    (3, "bignint", &["bstr", "bytes"]),
+    (24, "encoded-cbor", &["bstr", "bytes"]),
+    (32, "uri", &["tstr", "text"]),
+    (33, "b64url", &["tstr", "text"]),
+    (34, "b64legacy", &["tstr", "text"]),
+    (35, "regexp", &["tstr", "text"]),
+    (36, "mime-message", &["tstr", "text"]),
+    (37, "bstr", &["bstr", "bytes"]),
+    (55799, "cbor-any", &["any"]),
+];
+
+/// How a well-known tag is represented.
+pub enum WellKnownTag<'b, 'a> {
+    /// The tag's natural representation, as a prelude type.
+    Prelude(&'static str),
+    /// The declared content type, when it differs from the one the tag is
+    /// defined over (e.g. `#6.1(tstr)`).
+    Content(&'b cddl::ast::Type<'a>),
+}
+
+/// The representation of a well-known tag, or `None` for other tags.
+pub fn well_known_tag<'b, 'a>(
+    tag: Option<u64>,
+    t: Option<&'b cddl::ast::Type<'a>>,
+) -> Option<WellKnownTag<'b, 'a>> {
+    let (_, prelude, content) = WELL_KNOWN_TAGS
+        .iter()
+        .find(|(number, ..)| Some(*number) == tag)?;
+    let Some(t) = t else {
+        return Some(WellKnownTag::Prelude(prelude));
+    };
+    let is_content = match t.type_choices.as_slice() {
+        [cddl::ast::TypeChoice {
+            type1:
+                cddl::ast::Type1 {
+                    type2:
+                        cddl::ast::Type2::Typename {
+                            ident,
+                            generic_args: None,
+                            ..
+                        },
+                    operator: None,
+                    ..
+                },
+            ..
+        }] => ident.socket.is_none() && content.contains(&ident.ident),
+        _ => false,
+    };
+    Some(if is_content {
+        WellKnownTag::Prelude(prelude)
+    } else {
+        WellKnownTag::Content(t)
+    })
+}
+
+/// The prelude type of a major type (e.g. `#0` or `#7.20`), if it has one.
+pub fn major_type_prelude(mt: u8, constraint: Option<u64>) -> Option<&'static str> {
+    match (mt, constraint) {
+        (0, _) => Some("uint"),
+        (1, _) => Some("nint"),
+        (2, _) => Some("bstr"),
+        (3, _) => Some("tstr"),
+        (7, Some(20)) => Some("false"),
+        (7, Some(21)) => Some("true"),
+        (7, Some(22)) => Some("null"),
+        (7, Some(23)) => Some("undefined"),
+        (7, Some(25..=27)) => Some("float"),
+        _ => None,
+    }
+}
+
+/// Names of the rules defined or used as sockets, i.e. with a `$` or `$$`.
+pub fn sockets(cddl: &cddl::ast::CDDL) -> BTreeSet<String> {
+    struct Collector(BTreeSet<String>);
+    impl<'a, 'b: 'a> cddl::visitor::Visitor<'a, 'b, cddl::Error> for Collector {
+        fn visit_identifier(
+            &mut self,
+            ident: &cddl::ast::Identifier<'a>,
+        ) -> cddl::visitor::Result<cddl::Error> {
+            if ident.socket.is_some() {
+                self.0.insert(ident.ident.to_string());
+            }
+            Ok(())
+        }
+    }
+    let mut collector = Collector(BTreeSet::new());
+    for rule in &cddl.rules {
+        let name = match rule {
+            cddl::ast::Rule::Type { rule, .. } => &rule.name,
+            cddl::ast::Rule::Group { rule, .. } => &rule.name,
+        };
+        if name.socket.is_some() {
+            collector.0.insert(name.ident.to_string());
+        }
+        let _ = cddl::visitor::walk_rule(&mut collector, rule);
+    }
+    collector.0
 }
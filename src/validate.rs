@@ -0,0 +1,235 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Validation of JSON and CBOR documents against the rules of a CDDL
+//! document.
+//!
+//! The validators of the `cddl` crate check documents against the first type
+//! rule. To check them against another rule, a copy of it is put in front of a
+//! copy of the CDDL, one choice at a time, so that mismatches are those of a
+//! single choice.
+
+use std::collections::HashSet;
+
+use anyhow::{anyhow, Result};
+use cddl::{
+    ast::{Identifier, Rule, Type2, TypeChoice, CDDL},
+    validator::{cbor, json, Validator},
+    visitor::{self, Visitor},
+};
+
+use crate::amendments::merge_amendments;
+
+/// Name of the copy of the rule checked against, which no CDDL rule can have.
+const ROOT: &str = "cddlconv root";
+
+/// A JSON or CBOR document.
+#[derive(Debug, Clone)]
+pub enum Instance {
+    Json(serde_json::Value),
+    Cbor(ciborium::value::Value),
+}
+
+impl Instance {
+    /// Parses `bytes` as CBOR if `cbor` is set, and as JSON otherwise.
+    pub fn parse(bytes: &[u8], cbor: bool) -> Result<Instance> {
+        Ok(if cbor {
+            Instance::Cbor(ciborium::de::from_reader(bytes)?)
+        } else {
+            Instance::Json(serde_json::from_slice(bytes)?)
+        })
+    }
+}
+
+/// A place where a document does not match a rule.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    /// JSON pointer to the mismatching value, empty for the whole document.
+    pub path: String,
+    pub reason: String,
+}
+
+/// Checks `instance` against the type rule named `rule`, and returns the
+/// places where it does not match. If it matches none of the choices of the
+/// rule (following references to rules that are choices), those of the
+/// choice with the fewest mismatches are returned.
+pub fn validate(cddl: &CDDL, rule: &str, instance: &Instance) -> Result<Vec<Mismatch>> {
+    let cddl = merge_amendments(cddl);
+    let root = type_rule(&cddl, rule).ok_or_else(|| anyhow!("no type rule is named `{}`", rule))?;
+    let mut choices = Vec::new();
+    flatten_choices(&cddl, rule, &mut HashSet::new(), &mut choices);
+    let mut closest: Option<Vec<Mismatch>> = None;
+    for choice in choices {
+        let mut root = root.clone();
+        if let Rule::Type { rule, .. } = &mut root {
+            rule.name.ident = ROOT;
+            rule.value.type_choices = vec![choice];
+        }
+        let mismatches = check(&cddl, root, instance)?;
+        if mismatches.is_empty() {
+            return Ok(mismatches);
+        }
+        if closest
+            .as_ref()
+            .is_none_or(|closest| mismatches.len() < closest.len())
+        {
+            closest = Some(mismatches);
+        }
+    }
+    Ok(closest.unwrap_or_default())
+}
+
+/// The non-generic type rule named `name`.
+fn type_rule<'c, 'a>(cddl: &'c CDDL<'a>, name: &str) -> Option<&'c Rule<'a>> {
+    cddl.rules.iter().find(|rule| match rule {
+        Rule::Type { rule, .. } => rule.name.ident == name && rule.generic_params.is_none(),
+        Rule::Group { .. } => false,
+    })
+}
+
+/// Adds the choices of the type rule `name` to `choices`, with those of the
+/// rules it is a choice of in place of their names.
+fn flatten_choices<'a>(
+    cddl: &CDDL<'a>,
+    name: &str,
+    visited: &mut HashSet<String>,
+    choices: &mut Vec<TypeChoice<'a>>,
+) {
+    let Some(Rule::Type { rule, .. }) = type_rule(cddl, name) else {
+        return;
+    };
+    visited.insert(name.to_string());
+    for choice in &rule.value.type_choices {
+        if let (
+            None,
+            Type2::Typename {
+                ident,
+                generic_args: None,
+                ..
+            },
+        ) = (&choice.type1.operator, &choice.type1.type2)
+        {
+            if ident.socket.is_none()
+                && !visited.contains(ident.ident)
+                && type_rule(cddl, ident.ident).is_some()
+            {
+                flatten_choices(cddl, ident.ident, visited, choices);
+                continue;
+            }
+        }
+        choices.push(choice.clone());
+    }
+}
+
+/// Checks `instance` against `root`, put in front of the rules of `cddl`.
+fn check(cddl: &CDDL, root: Rule, instance: &Instance) -> Result<Vec<Mismatch>> {
+    let mut cddl = cddl.clone();
+    cddl.rules.insert(0, root);
+
+    match instance {
+        Instance::Json(value) => {
+            let mut validator = json::JSONValidator::new(&cddl, value.clone(), None);
+            match validator.validate() {
+                Ok(()) => Ok(Vec::new()),
+                Err(json::Error::Validation(errors)) => Ok(errors
+                    .into_iter()
+                    .map(|error| Mismatch {
+                        path: error.json_location,
+                        reason: error.reason,
+                    })
+                    .collect()),
+                Err(error) => Err(anyhow!(error.to_string())),
+            }
+        }
+        Instance::Cbor(value) => {
+            let mut validator = cbor::CBORValidator::new(&cddl, value.clone(), None);
+            match validator.validate() {
+                Ok(()) => Ok(Vec::new()),
+                Err(cbor::Error::Validation(errors)) => Ok(errors
+                    .into_iter()
+                    .map(|error| Mismatch {
+                        path: error.cbor_location,
+                        reason: error.reason,
+                    })
+                    .collect()),
+                Err(error) => Err(anyhow!(error.to_string())),
+            }
+        }
+    }
+}
+
+/// Returns the names of the type rules that `instance` matches, in order.
+/// Only rules that no other rule uses are tried, or the first rule if every
+/// rule is used. Generic rules, sockets and rules the validators fail on are
+/// skipped.
+pub fn matching_rules<'a>(cddl: &CDDL<'a>, instance: &Instance) -> Vec<&'a str> {
+    let used = used_rules(cddl);
+    let candidates = cddl
+        .rules
+        .iter()
+        .filter_map(|rule| match rule {
+            Rule::Type { rule, .. } => Some(rule),
+            Rule::Group { .. } => None,
+        })
+        .filter(|rule| rule.generic_params.is_none() && rule.name.socket.is_none())
+        .collect::<Vec<_>>();
+    let roots = candidates
+        .iter()
+        .filter(|rule| !used.contains(rule.name.ident))
+        .collect::<Vec<_>>();
+    let roots = if roots.is_empty() {
+        candidates.iter().take(1).collect()
+    } else {
+        roots
+    };
+    let mut names = Vec::new();
+    for rule in roots {
+        if names.contains(&rule.name.ident) {
+            continue;
+        }
+        let mismatches = validate(cddl, rule.name.ident, instance);
+        if mismatches.is_ok_and(|mismatches| mismatches.is_empty()) {
+            names.push(rule.name.ident);
+        }
+    }
+    names
+}
+
+/// Names of the rules that other rules use.
+fn used_rules(cddl: &CDDL) -> HashSet<String> {
+    struct Collector<'a> {
+        rule: &'a str,
+        used: HashSet<String>,
+    }
+    impl<'a, 'b: 'a> Visitor<'a, 'b, cddl::Error> for Collector<'_> {
+        fn visit_identifier(&mut self, ident: &Identifier<'a>) -> visitor::Result<cddl::Error> {
+            if ident.ident != self.rule {
+                self.used.insert(ident.ident.to_string());
+            }
+            Ok(())
+        }
+    }
+    let mut collector = Collector {
+        rule: "",
+        used: HashSet::new(),
+    };
+    for rule in &cddl.rules {
+        collector.rule = match rule {
+            Rule::Type { rule, .. } => rule.name.ident,
+            Rule::Group { rule, .. } => rule.name.ident,
+        };
+        let _ = visitor::walk_rule(&mut collector, rule);
+    }
+    collector.used
+}
@@ -0,0 +1,167 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Group rules used as elements of arrays, e.g. `pair` in `[* pair]` with
+//! `pair = (key: text, value: int)`.
+//!
+//! Such a group stands for a map in a map but for a sequence of elements in
+//! an array. With [`crate::options::Options::vector_groups`], engines emit a
+//! tuple (e.g. `PairVector`) for the group rules used in arrays, and arrays
+//! of repeated groups are flat arrays of their elements.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use cddl::ast::{Group, GroupChoice, GroupEntry, Occur, Occurrence, Rule, Type2, CDDL};
+
+use crate::rewrite::{walk_type2, Rewriter};
+
+/// Returns the names of the group rules used in arrays, directly or through
+/// other groups used in arrays.
+pub fn array_groups(cddl: &CDDL) -> BTreeSet<String> {
+    let groups = group_rules(cddl);
+    let mut collector = Collector {
+        groups: &groups,
+        names: Vec::new(),
+    };
+    for rule in &cddl.rules {
+        collector.rewrite_rule(&mut rule.clone());
+    }
+    let mut names = BTreeSet::new();
+    while let Some(name) = collector.names.pop() {
+        if names.insert(name.clone()) {
+            collector.add_entry(groups[name.as_str()]);
+        }
+    }
+    names
+}
+
+/// Numbers of elements of group rules, keyed by name, or `None` for those
+/// whose number of elements varies. Other rules stand for one element.
+pub type Lengths = BTreeMap<String, Option<usize>>;
+
+/// Returns the numbers of elements of the group rules in `names`.
+pub fn array_lengths(cddl: &CDDL, names: &BTreeSet<String>) -> Lengths {
+    let groups = group_rules(cddl);
+    let mut lengths: Lengths = names.iter().map(|name| (name.clone(), None)).collect();
+    // Groups may use groups defined further down, so lengths are computed
+    // until no more are found.
+    loop {
+        let mut found = false;
+        for name in names {
+            if lengths[name].is_some() {
+                continue;
+            }
+            let length = groups
+                .get(name.as_str())
+                .and_then(|entry| entry_length(entry, &lengths));
+            if length.is_some() {
+                lengths.insert(name.clone(), length);
+                found = true;
+            }
+        }
+        if !found {
+            return lengths;
+        }
+    }
+}
+
+/// The number of elements a group stands for in an array, if it is the same
+/// for all of its choices.
+pub fn group_length(group: &Group, lengths: &Lengths) -> Option<usize> {
+    let mut choices = group
+        .group_choices
+        .iter()
+        .map(|choice| choice_length(choice, lengths));
+    let length = choices.next()??;
+    choices.all(|other| other == Some(length)).then_some(length)
+}
+
+fn choice_length(choice: &GroupChoice, lengths: &Lengths) -> Option<usize> {
+    choice
+        .group_entries
+        .iter()
+        .map(|(entry, _)| entry_length(entry, lengths))
+        .sum()
+}
+
+fn entry_length(entry: &GroupEntry, lengths: &Lengths) -> Option<usize> {
+    match entry {
+        GroupEntry::ValueMemberKey { ge, .. } => fixed_occurrence(&ge.occur),
+        GroupEntry::TypeGroupname { ge, .. } => {
+            let length = lengths.get(ge.name.ident).copied().unwrap_or(Some(1));
+            Some(fixed_occurrence(&ge.occur)? * length?)
+        }
+        GroupEntry::InlineGroup { occur, group, .. } => {
+            Some(fixed_occurrence(occur)? * group_length(group, lengths)?)
+        }
+    }
+}
+
+fn fixed_occurrence(occur: &Option<Occurrence>) -> Option<usize> {
+    match occur.as_ref().map(|occur| &occur.occur) {
+        None => Some(1),
+        Some(Occur::Exact {
+            lower: Some(lower),
+            upper: Some(upper),
+            ..
+        }) if lower == upper => Some(*lower),
+        _ => None,
+    }
+}
+
+fn group_rules<'c, 'a>(cddl: &'c CDDL<'a>) -> BTreeMap<&'a str, &'c GroupEntry<'a>> {
+    cddl.rules
+        .iter()
+        .filter_map(|rule| match rule {
+            Rule::Group { rule, .. } => Some((rule.name.ident, &rule.entry)),
+            Rule::Type { .. } => None,
+        })
+        .collect()
+}
+
+/// Collects the group rules that are elements of arrays.
+struct Collector<'g, 'a> {
+    groups: &'g BTreeMap<&'a str, &'g GroupEntry<'a>>,
+    names: Vec<String>,
+}
+
+impl<'a> Collector<'_, 'a> {
+    /// Adds the group rules an array element stands for.
+    fn add_entry(&mut self, entry: &GroupEntry) {
+        match entry {
+            GroupEntry::TypeGroupname { ge, .. } if self.groups.contains_key(ge.name.ident) => {
+                self.names.push(ge.name.ident.to_string());
+            }
+            GroupEntry::InlineGroup { group, .. } => self.add_group(group),
+            _ => {}
+        }
+    }
+
+    fn add_group(&mut self, group: &Group) {
+        for choice in &group.group_choices {
+            for (entry, _) in &choice.group_entries {
+                self.add_entry(entry);
+            }
+        }
+    }
+}
+
+impl<'a> Rewriter<'a> for Collector<'_, 'a> {
+    fn rewrite_type2(&mut self, t2: &mut Type2<'a>) {
+        if let Type2::Array { group, .. } = t2 {
+            self.add_group(group);
+        }
+        walk_type2(self, t2);
+    }
+}
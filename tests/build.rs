@@ -0,0 +1,86 @@
+use std::path::PathBuf;
+
+use cddlconv::{build::Builder, Target};
+
+/// Generated by `it_generates_rust` from `examples/rust.cddl`.
+#[allow(dead_code)]
+mod generated {
+    include!("generated/rust.rs");
+}
+
+/// An output directory of its own for the test `name`.
+fn out_dir(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("cddlconv-{}-{}", name, std::process::id()))
+}
+
+#[test]
+fn it_writes_to_the_output_directory() {
+    let out_dir = out_dir("it_writes_to_the_output_directory");
+    let paths = Builder::new()
+        .file("examples/naming.cddl")
+        .target(Target::Zod)
+        .out_dir(&out_dir)
+        .generate()
+        .unwrap();
+    assert_eq!(paths, [out_dir.join("naming.ts")]);
+    let text = std::fs::read_to_string(&paths[0]).unwrap();
+    assert!(text.contains("export const FooBar2Schema = "));
+}
+
+#[test]
+fn it_generates_rust() {
+    let out_dir = out_dir("it_generates_rust");
+    let paths = Builder::new()
+        .file("examples/rust.cddl")
+        .target(Target::Rust)
+        .out_dir(&out_dir)
+        .generate()
+        .unwrap();
+    assert_eq!(paths, [out_dir.join("rust.rs")]);
+    let text = std::fs::read_to_string(&paths[0]).unwrap();
+    assert_eq!(text, include_str!("generated/rust.rs"));
+}
+
+#[test]
+fn it_deserializes_with_the_generated_rust() {
+    let json = serde_json::json!({
+        "id": 1,
+        "label": null,
+        "type": "text-node",
+        "children": [],
+        "position": [0.5, 1.5],
+        "style": { "color": "red" },
+        "data-x": true,
+    });
+    let node: generated::Node = serde_json::from_value(json.clone()).unwrap();
+    assert_eq!(node.label, None);
+    assert_eq!(node.r#type, generated::Kind::TextNode);
+    assert_eq!(node.attributes.extra["data-x"], serde_json::json!(true));
+    let value: generated::Value = serde_json::from_value(serde_json::json!([1, [2]])).unwrap();
+    assert!(matches!(value, generated::Value::Variant3(_)));
+    let list: generated::List =
+        serde_json::from_value(serde_json::json!({ "head": 1, "tail": { "head": 2 } })).unwrap();
+    assert_eq!(list.tail.unwrap().head, 2);
+}
+
+#[test]
+fn it_tells_variants_apart_by_their_literal_members() {
+    let json = serde_json::json!({ "type": "error", "code": 1, "value": "x" });
+    let message: generated::Message = serde_json::from_value(json).unwrap();
+    assert!(matches!(message, generated::Message::Failure(_)));
+    let json = serde_json::json!({ "type": "success", "value": "x" });
+    let message: generated::Message = serde_json::from_value(json.clone()).unwrap();
+    assert!(matches!(message, generated::Message::Success(_)));
+    assert_eq!(serde_json::to_value(&message).unwrap(), json);
+    let json = serde_json::json!({ "type": "pending", "value": "x" });
+    assert!(serde_json::from_value::<generated::Message>(json).is_err());
+}
+
+#[test]
+fn it_accepts_anything_in_sockets_without_plugs() {
+    let json = serde_json::json!({ "kind": "click", "data": { "x": 1 }, "button": 2 });
+    let click: generated::Click = serde_json::from_value(json.clone()).unwrap();
+    assert_eq!(click.data, Some(serde_json::json!({ "x": 1 })));
+    assert_eq!(click.click_extension["button"], serde_json::json!(2));
+    assert_eq!(serde_json::to_value(&click).unwrap(), json);
+}
@@ -0,0 +1,65 @@
+use std::path::{Path, PathBuf};
+
+use cddlconv::config::Config;
+use cddlconv::options::{Brands, Extensibility, Quotes};
+
+#[test]
+fn it_groups_targets_by_input() {
+    let config: Config = r#"
+        input = "spec.cddl"
+
+        [options]
+        extensibility = "closed"
+        quotes = "single"
+        rename.rules = { js-uint = "JsSafeUint", Info = "ContextInfo" }
+
+        [[target]]
+        format = "typescript"
+        output = "gen/types.ts"
+        options = { type-guards = true, quotes = "double" }
+
+        [[target]]
+        input = "other.cddl"
+        format = "zod"
+
+        [[target]]
+        format = "zod"
+        output = "gen/schemas.ts"
+        options = { brand = ["id"], rename.rules = { Info = "Info" } }
+    "#
+    .parse()
+    .unwrap();
+    let conversions = config.relative_to(Path::new("root")).conversions().unwrap();
+
+    assert_eq!(conversions.len(), 2);
+    assert_eq!(conversions[0].input, PathBuf::from("root/spec.cddl"));
+    assert_eq!(conversions[1].input, PathBuf::from("root/other.cddl"));
+
+    let [types, schemas] = &conversions[0].outputs[..] else {
+        panic!("expected two outputs");
+    };
+    assert_eq!(types.format, "typescript");
+    assert_eq!(types.path, Some(PathBuf::from("root/gen/types.ts")));
+    assert!(types.options.type_guards);
+    assert_eq!(types.options.extensibility, Extensibility::Closed);
+    assert_eq!(types.options.layout.quotes, Quotes::Double);
+    assert_eq!(schemas.format, "zod");
+    assert_eq!(schemas.options.layout.quotes, Quotes::Single);
+    assert!(matches!(&schemas.options.brands, Brands::Only(names) if names == &["id"]));
+    assert_eq!(types.options.renames.rule("Info"), "ContextInfo");
+    assert_eq!(schemas.options.renames.rule("Info"), "Info");
+    assert_eq!(schemas.options.renames.rule("js-uint"), "JsSafeUint");
+
+    assert_eq!(conversions[1].outputs[0].path, None);
+}
+
+#[test]
+fn it_rejects_targets_without_input() {
+    let config: Config = "[[target]]\nformat = \"zod\"".parse().unwrap();
+    assert!(config.conversions().is_err());
+}
+
+#[test]
+fn it_rejects_unknown_options() {
+    assert!("[options]\ntype-guard = true".parse::<Config>().is_err());
+}
@@ -0,0 +1,17 @@
+use cddlconv::{convert, options::Options, Target};
+
+#[test]
+fn it_converts_text() {
+    let input = std::fs::read_to_string("examples/naming.cddl").unwrap();
+    let output = convert(&input, Target::Zod, &Options::default()).unwrap();
+    assert!(output.text.contains("export const FooBar2Schema = "));
+    assert_eq!(
+        output.diagnostics[0].message,
+        "`foo_bar` is named `FooBar2` since `FooBar` is the name of `foo-bar`"
+    );
+}
+
+#[test]
+fn it_rejects_invalid_cddl() {
+    assert!(convert("id = ", Target::TypeScript, &Options::default()).is_err());
+}
@@ -0,0 +1,148 @@
+/// Maps are structs.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Node {
+    pub id: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    pub r#type: Kind,
+    pub children: Vec<Node>,
+    pub position: (f64, f64),
+    pub style: NodeStyle,
+    #[serde(flatten)]
+    pub attributes: Attributes,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct NodeStyle {
+    pub color: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub width: Option<u64>,
+}
+
+/// Text values are enums.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum Kind {
+    #[serde(rename = "element")]
+    Element,
+    #[serde(rename = "text-node")]
+    TextNode,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Attributes {
+    #[serde(flatten)]
+    pub extra: std::collections::BTreeMap<String, serde_json::Value>,
+}
+
+/// Other choices are untagged enums.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(untagged)]
+pub enum Value {
+    Node(Node),
+    Int(i64),
+    Variant3(Vec<Value>),
+}
+
+/// Choices of maps are enums of structs.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(untagged)]
+pub enum Event {
+    Variant1(EventVariant1),
+    Variant2(EventVariant2),
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct EventVariant1 {
+    pub created: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct EventVariant2 {
+    pub deleted: u64,
+}
+
+/// Members that contain their map are boxed.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct List {
+    pub head: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tail: Option<Box<List>>,
+}
+
+/// Literal members only accept their value, which tells variants apart.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(untagged)]
+pub enum Message {
+    Success(Success),
+    Failure(Failure),
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Success {
+    pub r#type: SuccessType,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct SuccessType;
+
+impl TryFrom<String> for SuccessType {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        if value == "success" {
+            Ok(Self)
+        } else {
+            Err(format!("expected \"success\", found {:?}", value))
+        }
+    }
+}
+
+impl From<SuccessType> for String {
+    fn from(_: SuccessType) -> Self {
+        "success".to_string()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Failure {
+    pub r#type: FailureType,
+    pub code: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct FailureType;
+
+impl TryFrom<String> for FailureType {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        if value == "error" {
+            Ok(Self)
+        } else {
+            Err(format!("expected \"error\", found {:?}", value))
+        }
+    }
+}
+
+impl From<FailureType> for String {
+    fn from(_: FailureType) -> Self {
+        "error".to_string()
+    }
+}
+
+/// Sockets without plugs accept any value or member.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Click {
+    pub kind: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<serde_json::Value>,
+    #[serde(flatten)]
+    pub click_extension: std::collections::BTreeMap<String, serde_json::Value>,
+}
@@ -0,0 +1,66 @@
+use std::io::Write;
+
+use cddl::ast::{Rule, CDDL};
+use cddlconv::{options::Options, Diagnostic, Generator, Registry};
+
+/// Lists the names of the rules.
+struct Names;
+
+impl Generator for Names {
+    fn generate(
+        &self,
+        cddl: &CDDL,
+        _source: &str,
+        _options: &Options,
+        out: &mut dyn Write,
+    ) -> anyhow::Result<Vec<Diagnostic>> {
+        for rule in &cddl.rules {
+            let name = match rule {
+                Rule::Type { rule, .. } => rule.name.ident,
+                Rule::Group { rule, .. } => rule.name.ident,
+            };
+            writeln!(out, "{}", name)?;
+        }
+        Ok(Vec::new())
+    }
+}
+
+#[test]
+fn it_generates_registered_formats() {
+    let input = std::fs::read_to_string("examples/naming.cddl").unwrap();
+    let cddl = cddl::parser::cddl_from_str(&input, true).unwrap();
+    let mut registry = Registry::default();
+    registry.register("names", Names);
+
+    let mut out = Vec::new();
+    let diagnostics = registry
+        .get("names")
+        .unwrap()
+        .generate(&cddl, &input, &Options::default(), &mut out)
+        .unwrap();
+    assert!(diagnostics.is_empty());
+    assert!(String::from_utf8(out).unwrap().starts_with("foo-bar\n"));
+    assert!(registry.get("other").is_none());
+    assert_eq!(
+        registry.names().map(|(name, _)| name).collect::<Vec<_>>(),
+        ["typescript", "zod", "rust", "names"]
+    );
+}
+
+#[test]
+fn it_returns_diagnostics() {
+    let input = std::fs::read_to_string("examples/naming.cddl").unwrap();
+    let cddl = cddl::parser::cddl_from_str(&input, true).unwrap();
+    let registry = Registry::default();
+
+    let mut out = Vec::new();
+    let diagnostics = registry
+        .get("type-script")
+        .unwrap()
+        .generate(&cddl, &input, &Options::default(), &mut out)
+        .unwrap();
+    assert_eq!(
+        diagnostics[0].message,
+        "`foo_bar` is named `FooBar2` since `FooBar` is the name of `foo-bar`"
+    );
+}
@@ -0,0 +1,44 @@
+use cddlconv::{
+    convert,
+    options::{Options, Profile},
+    Target,
+};
+
+macro_rules! test {
+    ($name:ident, $input:expr) => {
+        test!($name, $input, Options::default());
+    };
+    ($name:ident, $input:expr, $options:expr) => {
+        #[test]
+        fn $name() {
+            let input = std::fs::read_to_string($input).unwrap();
+            let output = convert(&input, Target::Rust, &$options).unwrap();
+            let diagnostics = output
+                .diagnostics
+                .iter()
+                .map(|diagnostic| format!("{}\n", diagnostic))
+                .collect::<String>();
+            insta::assert_snapshot!(diagnostics);
+            insta::assert_snapshot!(output.text);
+        }
+    };
+}
+
+test!(it_works, "examples/webdriver-bidi/webdriver-bidi.cddl");
+test!(it_works_with_maps, "examples/rfc-examples/maps.cddl");
+test!(it_works_with_arrays, "examples/rfc-examples/arrays.cddl");
+test!(it_works_with_rust_types, "examples/rust.cddl");
+test!(it_works_with_generics, "examples/generics.cddl");
+test!(it_works_with_enums, "examples/enums.cddl");
+test!(it_works_with_naming, "examples/naming.cddl");
+test!(it_works_with_extensibility, "examples/extensibility.cddl");
+test!(it_works_with_sockets, "examples/sockets.cddl");
+test!(it_works_with_tags, "examples/tags.cddl");
+test!(
+    it_works_with_the_cbor_profile,
+    "examples/profiles.cddl",
+    Options {
+        profile: Profile::Cbor,
+        ..Default::default()
+    }
+);
@@ -0,0 +1,50 @@
+use cddlconv::validate::{matching_rules, validate, Instance, Mismatch};
+
+fn schema() -> String {
+    std::fs::read_to_string("examples/validation.cddl").unwrap()
+}
+
+#[test]
+fn it_reports_mismatches() {
+    let input = schema();
+    let cddl = cddl::parser::cddl_from_str(&input, true).unwrap();
+    let instance = Instance::parse(br#"{"id": 1, "method": 3, "params": {}}"#, false).unwrap();
+    assert_eq!(
+        validate(&cddl, "command", &instance).unwrap(),
+        [Mismatch {
+            path: "/method".to_string(),
+            reason: "expected type text, got 3".to_string(),
+        }]
+    );
+    assert!(validate(&cddl, "unknown", &instance).is_err());
+}
+
+#[test]
+fn it_reports_mismatches_of_the_closest_choice() {
+    let input = schema();
+    let cddl = cddl::parser::cddl_from_str(&input, true).unwrap();
+    let instance = Instance::parse(br#"{"type": "event", "method": 3}"#, false).unwrap();
+    assert_eq!(
+        validate(&cddl, "message", &instance).unwrap(),
+        [Mismatch {
+            path: "/method".to_string(),
+            reason: "expected type text, got 3".to_string(),
+        }]
+    );
+}
+
+#[test]
+fn it_detects_matching_rules() {
+    let input = schema();
+    let cddl = cddl::parser::cddl_from_str(&input, true).unwrap();
+    let json = Instance::parse(br#"{"type": "event", "method": "a"}"#, false).unwrap();
+    assert_eq!(matching_rules(&cddl, &json), ["message"]);
+    let mut bytes = Vec::new();
+    ciborium::ser::into_writer(
+        &serde_json::json!({"id": 1, "method": "a", "params": {}}),
+        &mut bytes,
+    )
+    .unwrap();
+    let cbor = Instance::parse(&bytes, true).unwrap();
+    assert_eq!(matching_rules(&cddl, &cbor), ["message"]);
+}
@@ -1,17 +1,31 @@
 use std::io::BufWriter;
 
 use cddl::visitor::Visitor;
-use cddlconv;
+use cddlconv::options::{
+    Brands, Extensibility, Layout, Options, Profile, Quotes, Renames, TrailingCommas,
+};
 
 macro_rules! test {
     ($name:ident, $input:expr) => {
+        test!($name, $input, Options::default());
+    };
+    ($name:ident, $input:expr, $options:expr) => {
         #[test]
         fn $name() {
             let input = std::fs::read_to_string($input).unwrap();
             let cddl = cddl::parser::cddl_from_str(&input, true).unwrap();
+            let options: Options = $options;
+            let mut names = Vec::new();
+            let cddl = if options.monomorphize {
+                cddlconv::monomorphize::monomorphize(&cddl, &mut names).unwrap()
+            } else {
+                cddl
+            };
             let stdout = BufWriter::new(Vec::new());
             let stderr = BufWriter::new(Vec::new());
-            let mut engine = cddlconv::engines::zod::Engine::with_writers(stdout, stderr);
+            let mut engine = cddlconv::engines::zod::Engine::with_writers(stdout, stderr)
+                .with_options(options)
+                .with_source(&input);
             engine.visit_cddl(&cddl).unwrap();
             engine.print_postamble();
 
@@ -44,3 +58,119 @@ test!(
     it_works_with_array_occurences,
     "examples/array_occurences.cddl"
 );
+test!(
+    it_works_with_brands,
+    "examples/brands.cddl",
+    Options {
+        brands: Brands::All,
+        ..Default::default()
+    }
+);
+test!(it_works_with_tags, "examples/tags.cddl");
+test!(
+    it_works_with_cbor_tags,
+    "examples/tags.cddl",
+    Options {
+        cbor_tags: true,
+        ..Default::default()
+    }
+);
+test!(it_works_with_json_profile, "examples/profiles.cddl");
+test!(
+    it_works_with_cbor_profile,
+    "examples/profiles.cddl",
+    Options {
+        profile: Profile::Cbor,
+        ..Default::default()
+    }
+);
+test!(it_works_with_sockets, "examples/sockets.cddl");
+test!(it_works_with_generics, "examples/generics.cddl");
+test!(
+    it_works_with_monomorphized_generics,
+    "examples/generics.cddl",
+    Options {
+        monomorphize: true,
+        ..Default::default()
+    }
+);
+test!(
+    it_works_with_discriminated_unions,
+    "examples/discriminated_unions.cddl"
+);
+test!(
+    it_works_with_group_composition,
+    "examples/group_composition.cddl"
+);
+test!(it_works_with_extensibility, "examples/extensibility.cddl");
+test!(
+    it_works_with_closed_maps,
+    "examples/extensibility.cddl",
+    Options {
+        extensibility: Extensibility::Closed,
+        ..Default::default()
+    }
+);
+test!(
+    it_works_with_strict_maps,
+    "examples/extensibility.cddl",
+    Options {
+        extensibility: Extensibility::Strict,
+        ..Default::default()
+    }
+);
+test!(it_works_with_unwrapping, "examples/unwrap.cddl");
+test!(
+    it_works_with_choice_amendments,
+    "examples/choice_amendments.cddl"
+);
+test!(
+    it_works_with_layout,
+    "examples/type_guards.cddl",
+    Options {
+        layout: Layout {
+            indent_width: 4,
+            line_width: 60,
+            trailing_commas: TrailingCommas::None,
+            quotes: Quotes::Single,
+        },
+        ..Default::default()
+    }
+);
+test!(
+    it_works_with_renames,
+    "examples/renames.cddl",
+    Options {
+        renames: Renames {
+            rules: [
+                ("js-uint", "JsSafeUint"),
+                ("browsingContext.BrowsingContext", "BrowsingContextId"),
+            ]
+            .map(|(rule, name)| (rule.to_string(), name.to_string()))
+            .into(),
+            properties: [
+                ("browsingContext.Info", "context", "browsingContextId"),
+                ("browsingContext.Info", "parent", "parentId"),
+                ("Attributes", "id", "nodeId"),
+            ]
+            .into_iter()
+            .fold(Default::default(), |mut properties, (rule, key, name)| {
+                properties
+                    .entry(rule.to_string())
+                    .or_insert_with(std::collections::BTreeMap::new)
+                    .insert(key.to_string(), name.to_string());
+                properties
+            }),
+        },
+        ..Default::default()
+    }
+);
+test!(it_works_with_naming, "examples/naming.cddl");
+test!(
+    it_works_with_vector_groups,
+    "examples/vector_groups.cddl",
+    Options {
+        vector_groups: true,
+        ..Default::default()
+    }
+);
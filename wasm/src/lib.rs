@@ -0,0 +1,65 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The JavaScript API of the WebAssembly build of `cddlconv`.
+//!
+//! ```js
+//! import { convert } from "cddlconv-wasm";
+//!
+//! const { text, diagnostics } = convert(cddl, "typescript", { "type-guards": true });
+//! ```
+
+#![cfg(target_arch = "wasm32")]
+
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+use cddlconv::{config::OptionsConfig, convert::parse, generator::Registry};
+
+/// The result of [`convert`], as a JavaScript object.
+#[derive(Serialize)]
+struct Output {
+    text: String,
+    diagnostics: Vec<String>,
+}
+
+/// Converts the CDDL text `cddl` to `format` (e.g. `typescript`, `zod` or `rust`).
+/// `options` are named as in a configuration file, e.g. `type-guards`, and
+/// may be `undefined`.
+#[wasm_bindgen]
+pub fn convert(cddl: &str, format: &str, options: JsValue) -> Result<JsValue, JsError> {
+    let options = if options.is_undefined() || options.is_null() {
+        OptionsConfig::default()
+    } else {
+        serde_wasm_bindgen::from_value::<OptionsConfig>(options)?
+    }
+    .to_options();
+    let registry = Registry::default();
+    let generator = registry
+        .get(format)
+        .ok_or_else(|| JsError::new(&format!("unknown format {}", format)))?;
+    let ast = parse(cddl).map_err(|error| JsError::new(&error.to_string()))?;
+    let mut text = Vec::new();
+    let diagnostics = generator
+        .generate(&ast, cddl, &options, &mut text)
+        .map_err(|error| JsError::new(&error.to_string()))?;
+    let output = Output {
+        text: String::from_utf8(text)?,
+        diagnostics: diagnostics
+            .into_iter()
+            .map(|diagnostic| diagnostic.message)
+            .collect(),
+    };
+    Ok(serde_wasm_bindgen::to_value(&output)?)
+}